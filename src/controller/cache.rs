@@ -0,0 +1,102 @@
+use inner::logger::Logger;
+use inner::{cache, helpers};
+use inner::list_helper::{print_header, print_table};
+use std::env;
+use std::fs::remove_dir_all;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn list(logger: Logger) {
+    let mirrors = match cache::mirrors() {
+        Ok(mirrors) => mirrors,
+        Err(e) => {
+            logger.fatal(format!("unable to read the cache: {}", e));
+            return
+        },
+    };
+    if mirrors.is_empty() {
+        logger.verbose("Cache", "no repository is cached");
+        return
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let rows: Vec<Vec<String>> = mirrors.iter().map(|mirror| vec![
+        mirror.name.clone(),
+        cache::format_size(mirror.size),
+        match now.saturating_sub(mirror.last_used) / 86400 {
+            0 => String::from("today"),
+            1 => String::from("1 day ago"),
+            days => format!("{} days ago", days),
+        },
+    ]).collect();
+    print_header("Cached repositories", mirrors.len());
+    print_table(&["Repository", "Size", "Last used"], &rows);
+    println!("\nTotal size: {}", cache::format_size(mirrors.iter().map(|mirror| mirror.size).sum()));
+}
+
+pub fn clean(no_prompt: bool, logger: Logger) {
+    let cache_dir = match cache::cache_dir() {
+        Some(cache_dir) => cache_dir,
+        None => {
+            logger.fatal("the cache is disabled");
+            return
+        },
+    };
+    if !cache_dir.is_dir() {
+        logger.verbose("Cache", "no repository is cached");
+        return
+    }
+
+    if !no_prompt {
+        match helpers::confirmation_prompt(format!("All repositories in `{}` will be deleted.\nDo you want to continue? [Y/n]", cache_dir.to_string_lossy()).as_str()) {
+            Ok(true) => (),
+            Ok(false) => {
+                logger.error("aborted");
+                return
+            },
+            Err(e) => {
+                logger.fatal(e);
+                return
+            },
+        }
+    }
+
+    match remove_dir_all(cache_dir.as_path()) {
+        Ok(_) => logger.verbose("Clean cache", cache_dir.to_string_lossy()),
+        Err(e) => logger.fatal(format!("unable to delete `{}`: {}", cache_dir.to_string_lossy(), e)),
+    }
+}
+
+pub fn gc(max_size: Option<&str>, logger: Logger) {
+    let max_size_str = max_size.map(|size| size.to_owned()).or_else(|| env::var(cache::MAX_SIZE_ENV).ok());
+    let max_size = match max_size_str {
+        Some(size) => match cache::parse_size(size.as_str()) {
+            Some(size) => size,
+            None => {
+                logger.fatal(format!("invalid cache size `{}`", size));
+                return
+            },
+        },
+        None => cache::DEFAULT_MAX_SIZE,
+    };
+
+    let mirrors = match cache::mirrors() {
+        Ok(mirrors) => mirrors,
+        Err(e) => {
+            logger.fatal(format!("unable to read the cache: {}", e));
+            return
+        },
+    };
+
+    let mut freed = 0;
+    for index in cache::evictions(&mirrors, max_size) {
+        let mirror = &mirrors[index];
+        match remove_dir_all(mirror.path.as_path()) {
+            Ok(_) => {
+                freed += mirror.size;
+                logger.verbose("Evict repository", &mirror.name);
+            },
+            Err(e) => logger.error(format!("unable to delete `{}`: {}", mirror.path.to_string_lossy(), e)),
+        }
+    }
+    logger.verbose("Collect garbage", format!("freed {} of the cache limited to {}", cache::format_size(freed), cache::format_size(max_size)));
+}
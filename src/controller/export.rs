@@ -0,0 +1,180 @@
+use inner::logger::Logger;
+use inner::{gomod, helpers, git_helper};
+use inner::vendor::VENDOR_DIR;
+use inner::gomod::{Require, Replace};
+use inner::manifest::{Manifest, Lock};
+use git2::{Repository, ObjectType};
+use std::fs::{File, create_dir_all};
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub fn export_gomod(with_vendor: bool, no_prompt: bool, logger: Logger) {
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.json`: {}", e));
+            return
+        },
+    };
+    let module_path = match manifest.info.import {
+        Some(import) => import,
+        None => {
+            logger.fatal("the `import` field of `info` in `rubigo.json` is required to name the module");
+            return
+        },
+    };
+    let lock = match Lock::load(Path::new("rubigo.lock")) {
+        Ok(lock) => lock,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
+            return
+        },
+    };
+
+    if !no_prompt && Path::new("go.mod").exists() {
+        match helpers::confirmation_prompt("The `go.mod` file already exists and will be overwritten.\nDo you want to continue? [Y/n]") {
+            Ok(true) => (),
+            Ok(false) => {
+                logger.error("aborted");
+                return
+            },
+            Err(e) => {
+                logger.fatal(e);
+                return
+            },
+        }
+    }
+
+    let mut requires = Vec::new();
+    let mut sums = Vec::new();
+    let mut failures = 0;
+    for pkg in &lock.git {
+        let pkg_path_buf = helpers::get_path_from_url(helpers::strip_url_scheme(pkg.import.as_str()).as_str(), Path::new(VENDOR_DIR));
+        let go_mod_path = pkg_path_buf.join("go.mod");
+        let (commit, commit_time) = match Repository::open(pkg_path_buf.as_path()) {
+            Ok(repo) => {
+                let commit = match pkg.commit {
+                    Some(ref commit) => Some(commit.clone()),
+                    None => git_helper::resolve_commit(&repo, pkg.version.as_str()),
+                };
+                let commit_time = commit.as_ref().and_then(|commit| repo.revparse_single(commit.as_str()).ok()).and_then(|obj| obj.peel(ObjectType::Commit).ok()).and_then(|obj| obj.into_commit().ok()).map(|commit| commit.time().seconds());
+                (commit, commit_time)
+            },
+            Err(_) => (pkg.commit.clone(), None),
+        };
+        let version = match gomod::module_version(pkg.version.as_str(), commit.as_deref(), commit_time, go_mod_path.is_file()) {
+            Some(version) => version,
+            None => {
+                logger.error(format!("unable to find a module version for `{}`, run `rubigo apply` to install it in `vendor` directory", pkg.import));
+                failures += 1;
+                continue
+            },
+        };
+
+        let replace = pkg.repo.as_ref().map(|repo| gomod::module_path_from_repo(repo.as_str())).and_then(|path| if path == pkg.import {
+            None
+        } else {
+            Some(Replace::Module(path, version.clone()))
+        });
+        let sum_path = match replace {
+            Some(Replace::Module(ref path, _)) => path.clone(),
+            _ => pkg.import.clone(),
+        };
+
+        let mut go_mod_contents = Vec::new();
+        if go_mod_path.is_file() {
+            if let Err(e) = File::open(go_mod_path.as_path()).and_then(|mut file| file.read_to_end(&mut go_mod_contents)) {
+                logger.error(format!("unable to read `go.mod` of `{}`: {}", pkg.import, e));
+                failures += 1;
+                continue
+            }
+        } else {
+            go_mod_contents = format!("module {}\n", sum_path).into_bytes();
+        }
+        match gomod::hash_module(pkg_path_buf.as_path(), sum_path.as_str(), version.as_str()) {
+            Ok(hash) => sums.push(format!("{} {} {}", sum_path, version, hash)),
+            Err(e) => {
+                logger.error(format!("unable to hash `{}`: {}", pkg.import, e));
+                failures += 1;
+                continue
+            },
+        }
+        sums.push(format!("{} {}/go.mod {}", sum_path, version, gomod::hash_go_mod(&go_mod_contents)));
+
+        requires.push(Require {
+            path: pkg.import.clone(),
+            version,
+            replace,
+        });
+    }
+
+    for import in &lock.local {
+        let pkg_path_buf = helpers::get_path_from_url(import.as_str(), Path::new(VENDOR_DIR));
+        if !pkg_path_buf.join("go.mod").is_file() {
+            logger.error(format!("the local package `{}` has no `go.mod` file which Go requires for a path replacement", import));
+        }
+        requires.push(Require {
+            path: import.clone(),
+            version: gomod::LOCAL_VERSION.to_owned(),
+            replace: Some(Replace::Dir(format!("./{}", pkg_path_buf.to_string_lossy().replace('\\', "/")))),
+        });
+    }
+
+    for import in &lock.global {
+        logger.error(format!("the global package `{}` is not exported, Go modules do not use `GOPATH/src`", import));
+    }
+
+    if failures > 0 {
+        logger.fatal(format!("unable to export {} package(s)", failures));
+        return
+    }
+
+    requires.sort_by(|a, b| a.path.cmp(&b.path));
+    sums.sort();
+
+    if !write_file("go.mod", gomod::go_mod(module_path.as_str(), &requires), &logger) {
+        return
+    }
+    let mut sum_contents = sums.join("\n");
+    if !sum_contents.is_empty() {
+        sum_contents.push('\n');
+    }
+    if !write_file("go.sum", sum_contents, &logger) {
+        return
+    }
+
+    if with_vendor {
+        let mut packages = Vec::new();
+        for req in &requires {
+            match gomod::packages(helpers::get_path_from_url(req.path.as_str(), Path::new(VENDOR_DIR)).as_path(), req.path.as_str()) {
+                Ok(pkgs) => packages.push(pkgs),
+                Err(e) => {
+                    logger.fatal(format!("unable to list the packages of `{}`: {}", req.path, e));
+                    return
+                },
+            }
+        }
+        if let Err(e) = create_dir_all(VENDOR_DIR) {
+            logger.fatal(format!("unable to create `vendor` directory: {}", e));
+            return
+        }
+        if !write_file(Path::new(VENDOR_DIR).join("modules.txt").to_string_lossy().as_ref(), gomod::modules_txt(&requires, &packages), &logger) {
+            return
+        }
+    }
+
+    logger.verbose("Export packages", format!("{} to `go.mod`", requires.len()));
+}
+
+fn write_file(file_name: &str, contents: String, logger: &Logger) -> bool {
+    match File::create(file_name).and_then(|mut file| file.write_all(contents.as_bytes())) {
+        Ok(_) => {
+            logger.verbose("Create file", file_name);
+            true
+        },
+        Err(e) => {
+            logger.fatal(format!("unable to write to `{}`: {}", file_name, e));
+            false
+        },
+    }
+}
@@ -0,0 +1,83 @@
+use inner::logger::Logger;
+use inner::{helpers, importer, imports};
+use inner::vendor::VENDOR_DIR;
+use inner::graph::Graph;
+use inner::resolver::ROOT_REQUIRER;
+use inner::manifest::{Manifest, Lock};
+use std::path::Path;
+
+pub fn why(import: &str, logger: Logger) {
+    let graph = match load_graph(&logger) {
+        Some(graph) => graph,
+        None => return,
+    };
+    let paths = graph.paths(import);
+    if paths.is_empty() {
+        logger.fatal(format!("the package `{}` is not required by the project", import));
+        return
+    }
+    for path in paths {
+        println!("{}", path.join(" -> "));
+    }
+}
+
+pub fn graph(format: &str, logger: Logger) {
+    let graph = match load_graph(&logger) {
+        Some(graph) => graph,
+        None => return,
+    };
+    match format {
+        "json" => println!("{:#}", graph.to_json()),
+        _ => print!("{}", graph.to_dot()),
+    }
+}
+
+fn load_graph(logger: &Logger) -> Option<Graph> {
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.json`: {}", e));
+            return None
+        },
+    };
+    let lock = match Lock::load(Path::new("rubigo.lock")) {
+        Ok(lock) => lock,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
+            return None
+        },
+    };
+
+    let mut graph = Graph::new(ROOT_REQUIRER);
+    for pkg in &manifest.packages.git {
+        graph.add_edge(ROOT_REQUIRER, pkg.import.as_str());
+    }
+    for import in manifest.packages.local.iter().chain(&manifest.packages.global) {
+        graph.add_edge(ROOT_REQUIRER, import.as_str());
+    }
+
+    let mut locked: Vec<&str> = lock.git.iter().map(|pkg| pkg.import.as_str()).collect();
+    locked.extend(lock.local.iter().map(|import| import.as_str()));
+    for import in &locked {
+        let pkg_path_buf = helpers::get_path_from_url(helpers::strip_url_scheme(import).as_str(), Path::new(VENDOR_DIR));
+        let deps = match importer::dependencies(pkg_path_buf.as_path()) {
+            Ok(deps) => deps,
+            Err(e) => {
+                logger.error(format!("unable to read the dependencies of `{}`: {}", import, e));
+                continue
+            },
+        };
+        for dep in deps.iter().filter(|dep| dep.import != *import) {
+            let target = locked.iter().find(|pkg| imports::provides(pkg, dep.import.as_str())).cloned().unwrap_or(dep.import.as_str());
+            graph.add_edge(import, target);
+        }
+    }
+
+    // Packages locked without a known requirer are attached through the parent recorded in the lock
+    for pkg in &lock.git {
+        if graph.paths(pkg.import.as_str()).is_empty() {
+            graph.add_edge(pkg.parent.as_deref().unwrap_or(ROOT_REQUIRER), pkg.import.as_str());
+        }
+    }
+    Some(graph)
+}
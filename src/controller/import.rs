@@ -0,0 +1,101 @@
+use inner::context::Context;
+use inner::logger::Logger;
+use inner::{importer, json_helper, helpers, vendor};
+use inner::importer::Tool;
+use inner::manifest::{Manifest, Info, Packages, Lock};
+use std::path::Path;
+use std::fs::remove_file;
+
+pub fn import(tool_name: Option<&str>, ctx: &Context, logger: Logger) {
+    if Path::new("rubigo.json").exists() {
+        logger.fatal("Rubigo project has already been initialized");
+        return
+    }
+
+    let tool = match tool_name {
+        Some(name) => match Tool::from_name(name) {
+            Some(tool) => tool,
+            None => {
+                logger.fatal(format!("unknown dependency tool `{}`", name));
+                return
+            },
+        },
+        None => match importer::detect(Path::new(".")) {
+            Some(tool) => tool,
+            None => {
+                logger.fatal("no manifest of a supported dependency tool was found in this directory");
+                return
+            },
+        },
+    };
+
+    import_tool(tool, true, ctx, logger);
+}
+
+// The packages of `go.mod` are installed to write the lock only if `should_install` is true
+pub fn import_tool(tool: Tool, should_install: bool, ctx: &Context, logger: Logger) {
+    let imported = match importer::import(tool, Path::new(".")) {
+        Ok(imported) => imported,
+        Err(e) => {
+            logger.fatal(format!("unable to import `{}`: {}", tool.manifest_file(), e));
+            return
+        },
+    };
+
+    for import in &imported.skipped {
+        logger.error(format!("the package `{0}` has no version in `{1}`, it could be installed using `rubigo get {0}`", import, tool.manifest_file()));
+    }
+    for warning in &imported.warnings {
+        logger.error(warning);
+    }
+
+    let manifest = Manifest {
+        info: Info {
+            name: Some(helpers::get_current_dir()),
+            import: imported.project_import,
+            ..Info::default()
+        },
+        packages: Packages {
+            git: imported.git,
+            local: Vec::new(),
+            global: Vec::new(),
+        },
+    };
+
+    match json_helper::write("rubigo.json", "", Some(manifest.to_json())) {
+        Ok(_) => logger.verbose("Create file", "rubigo.json"),
+        Err(e) => {
+            logger.fatal(format!("unable to write to `rubigo.json`: {}", e));
+            return
+        },
+    }
+
+    let lock_json = match tool {
+        Tool::Gomod if !should_install => {
+            logger.verbose("Import packages", format!("{} from `{}`", manifest.packages.git.len(), tool.manifest_file()));
+            return
+        },
+        Tool::Gomod => object!{
+            json_helper::GIT_KEY => vendor::install_git_packages(&manifest.to_json()[json_helper::PACKAGES_KEY][json_helper::GIT_KEY], "Install package", false, false, false, ctx, logger),
+            json_helper::LOCAL_KEY => array![],
+            json_helper::GLOBAL_KEY => array![]
+        },
+        _ => Lock {
+            git: imported.lock,
+            ..Lock::default()
+        }.to_json(),
+    };
+
+    match json_helper::write("rubigo.lock", "", Some(lock_json)) {
+        Ok(_) => logger.verbose("Create file", "rubigo.lock"),
+        Err(e) => {
+            if remove_file("rubigo.json").is_ok() {
+                logger.verbose("Delete file", "rubigo.json");
+            }
+            logger.fatal(format!("unable to write to `rubigo.lock`: {}", e));
+            return
+        },
+    }
+
+    logger.verbose("Import packages", format!("{} from `{}`", manifest.packages.git.len(), tool.manifest_file()));
+}
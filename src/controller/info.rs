@@ -3,7 +3,7 @@ use inner::json_helper;
 use std::path::Path;
 
 pub fn display(logger: &Logger) {
-    let content = match json_helper::read(Path::new("rubigo.json")) {
+    let content = match json_helper::read_manifest(Path::new("rubigo.json")) {
         Ok(content) => content,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.json`: {}", e));
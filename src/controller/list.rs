@@ -5,7 +5,7 @@ use json::JsonValue;
 use inner::list_helper::{print_header, print_git_packages, print_str_packages};
 
 pub fn list(is_local: bool, is_remote: bool, is_global: bool, logger: &Logger) {
-    let lock_content = match json_helper::read(Path::new("rubigo.lock")) {
+    let lock_content = match json_helper::read_lock(Path::new("rubigo.lock")) {
         Ok(content) => content,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
@@ -2,3 +2,11 @@ pub mod project;
 pub mod package;
 pub mod info;
 pub mod list;
+pub mod validate;
+pub mod verify;
+pub mod import;
+pub mod export;
+pub mod tidy;
+pub mod outdated;
+pub mod graph;
+pub mod cache;
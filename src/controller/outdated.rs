@@ -0,0 +1,157 @@
+use inner::context::Context;
+use inner::logger::Logger;
+use inner::{git_helper, helpers, json_helper, vcs, vendor};
+use inner::list_helper::format_table;
+use inner::manifest::{Manifest, Lock, LockedPackage};
+use git2::Repository;
+use semver::VersionReq;
+use json::JsonValue;
+use std::path::Path;
+use std::sync::mpsc::channel;
+
+struct Outdated {
+    import: String,
+    locked: String,
+    wanted: Option<String>,
+    latest: Option<String>,
+    branch: Option<String>,
+    behind: Option<usize>,
+}
+
+impl Outdated {
+    fn to_json(&self) -> JsonValue {
+        object!{
+            json_helper::IMPORT_KEY => self.import.as_str(),
+            "locked" => self.locked.as_str(),
+            "wanted" => self.wanted.clone(),
+            "latest" => self.latest.clone(),
+            "branch" => self.branch.clone(),
+            "behind" => self.behind
+        }
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("-"));
+        vec![
+            self.import.clone(),
+            self.locked.clone(),
+            or_dash(&self.wanted),
+            or_dash(&self.latest),
+            match (&self.branch, self.behind) {
+                (Some(branch), Some(behind)) => format!("{} ({})", behind, branch),
+                _ => String::from("-"),
+            },
+        ]
+    }
+}
+
+pub fn outdated(as_json: bool, ctx: &Context, logger: Logger) {
+    if let Some(report) = report(as_json, ctx, logger) {
+        print!("{}", report);
+    }
+}
+
+// Returns the table (or JSON) of the packages, or `None` if it failed
+pub fn report(as_json: bool, ctx: &Context, logger: Logger) -> Option<String> {
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.json`: {}", e));
+            return None
+        },
+    };
+    let lock = match Lock::load(Path::new("rubigo.lock")) {
+        Ok(lock) => lock,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
+            return None
+        },
+    };
+
+    let pool = match helpers::new_thread_pool(ctx) {
+        Ok(pool) => pool,
+        Err(e) => {
+            logger.fatal(e);
+            return None
+        },
+    };
+    let (tx, rx) = channel();
+    for pkg in lock.git.iter().cloned() {
+        let constraint = manifest.packages.git.iter().find(|p| p.import == pkg.import).map(|p| (p.version.clone(), p.exclude.clone()));
+        let c_tx = tx.clone();
+        let c_ctx = ctx.clone();
+        pool.execute(move || {
+            let _ = c_tx.send(check_package(pkg, constraint, &c_ctx, logger));
+        });
+    }
+
+    let mut packages: Vec<Outdated> = rx.iter().take(lock.git.len()).collect();
+    packages.sort_by(|a, b| a.import.cmp(&b.import));
+
+    if as_json {
+        let mut packages_json = array![];
+        for pkg in &packages {
+            let _ = packages_json.push(pkg.to_json());
+        }
+        Some(format!("{:#}\n", packages_json))
+    } else if !packages.is_empty() {
+        let rows: Vec<Vec<String>> = packages.iter().map(|pkg| pkg.to_row()).collect();
+        Some(format_table(&["Package", "Locked", "Wanted", "Latest", "Behind"], &rows))
+    } else {
+        Some(String::new())
+    }
+}
+
+fn check_package(pkg: LockedPackage, constraint: Option<(String, Vec<String>)>, ctx: &Context, logger: Logger) -> Outdated {
+    let mut outdated = Outdated {
+        import: pkg.import.clone(),
+        locked: pkg.version.clone(),
+        wanted: None,
+        latest: None,
+        branch: None,
+        behind: None,
+    };
+
+    let (_, pkg_path_buf, kind) = match vendor::locate_package(helpers::strip_url_scheme(pkg.import.as_str()).as_str(), pkg.repo.as_deref(), pkg.vcs.as_deref(), ctx) {
+        Ok(location) => location,
+        Err(e) => {
+            logger.error(format!("unable to locate `{}`: {}", pkg.import, e));
+            return outdated
+        },
+    };
+    let repo = match vcs::open(kind, pkg_path_buf.as_path(), ctx, logger) {
+        Ok(repo) => vcs::for_import(repo, pkg.import.as_str()),
+        Err(e) => {
+            logger.error(format!("unable to open the repository of `{}`: {}", pkg.import, e));
+            return outdated
+        },
+    };
+    match repo.fetch() {
+        Ok(_) => logger.verbose("Fetch repository", &pkg.import),
+        Err(e) => logger.error(format!("unable to fetch `{}`: {}", pkg.import, e)),
+    }
+
+    let tags = repo.tags().unwrap_or_default();
+    outdated.latest = vcs::latest_version(&tags, None, &[]).map(|latest| latest.0);
+    outdated.wanted = match constraint {
+        Some((ref version, ref exclude)) => match VersionReq::parse(version.as_str()) {
+            Ok(rule) => vcs::latest_version(&tags, Some(&rule), exclude).map(|wanted| wanted.0),
+            Err(_) => Some(version.clone()),
+        },
+        None => None,
+    };
+
+    // Counting the commits behind a branch is only supported by git repositories
+    if kind != vcs::Kind::Git {
+        return outdated
+    }
+    let commit = pkg.commit.clone().or_else(|| repo.resolve_revision(pkg.version.as_str()));
+    if let (Some(commit), Ok(git_repo)) = (commit, Repository::open(pkg_path_buf.as_path())) {
+        let branch = constraint.as_ref().map(|c| c.0.as_str());
+        if let Some((branch, behind)) = git_helper::commits_behind(&git_repo, commit.as_str(), branch).or_else(|| git_helper::commits_behind(&git_repo, commit.as_str(), None)) {
+            outdated.branch = Some(branch);
+            outdated.behind = Some(behind);
+        }
+    }
+    outdated
+}
@@ -1,340 +1,259 @@
+use inner::context::Context;
 use inner::logger::Logger;
 use futures::Future;
 use futures_cpupool::CpuPool;
-use inner::{json_helper, vendor, go, helpers, git_helper};
+use inner::{config, json_helper, vendor, go, helpers, importer, integrity, vcs};
+use inner::staging::{self, Staging};
+use inner::manifest::{self, Manifest, GitPackage, LockedPackage, Lock};
 use std::path::Path;
 use json::JsonValue;
 use std::sync::mpsc::channel;
 use std::thread;
 use std::fs::{create_dir_all, remove_dir_all};
 use controller::project;
-use git2::{Repository, ResetType};
 use std::process;
 
-pub fn get(mut package_url: &str, repo_url: Option<&str>, no_prompt: bool, is_global: bool, is_local: bool, logger: Logger) {
+pub fn get(package_url: &str, repo_url: Option<&str>, vcs_name: Option<&str>, no_prompt: bool, is_global: bool, is_local: bool, ctx: &Context, logger: Logger) {
+    if let Err(e) = try_get(package_url, repo_url, vcs_name, no_prompt, is_global, is_local, ctx, logger) {
+        logger.fatal(e);
+    }
+}
+
+// Returns the error instead of exiting, so the callers which add several packages could add the other ones
+pub fn try_get(mut package_url: &str, repo_url: Option<&str>, vcs_name: Option<&str>, no_prompt: bool, is_global: bool, is_local: bool, ctx: &Context, logger: Logger) -> Result<(), String> {
     if package_url.ends_with("/") || package_url.ends_with("\\") {
         package_url = &package_url[..package_url.len() - 1];
     }
 
     if !Path::new("rubigo.json").exists() {
         if no_prompt {
-            project::init(logger);
+            project::init(ctx, logger);
         } else {
             match helpers::confirmation_prompt("The `rubigo.json` file was not found in this directory, it seems that Rubigo project has not been initialized.\nDo you want to initialize it? [Y/n]") {
                 Ok(state) => if state {
-                    project::init(logger);
+                    project::init(ctx, logger);
                 } else {
-                    logger.fatal("Rubigo project has not been initialized");
-                    return
+                    return Err(String::from("Rubigo project has not been initialized"))
                 },
                 Err(e) => {
-                    logger.fatal(e);
-                    return
+                    return Err(e.to_string())
                 },
             }
         }
     }
 
-    let rubigo_json = match json_helper::read(Path::new("rubigo.json")) {
-        Ok(content_json) => content_json,
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
         Err(e) => {
-            logger.fatal(format!("unable to read `rubigo.json`: {}", e));
-            return
+            return Err(format!("unable to read `rubigo.json`: {}", e))
         },
     };
 
     let pool = CpuPool::new(1);
-    let rubigo_lock_future = pool.spawn_fn(|| {
-        match json_helper::read(Path::new("rubigo.lock")) {
-            Ok(content_json) => Ok(content_json),
-            Err(e) => Err(e),
-        }
-    });
+    let lock_future = pool.spawn_fn(|| Lock::load(Path::new("rubigo.lock")));
 
     let pkg_import = helpers::strip_url_scheme(package_url);
-    let mut pkg_path_buf = helpers::get_path_from_url(&pkg_import);
+    let mut pkg_path_buf = helpers::get_path_from_url(&pkg_import, ctx.vendor_dir.as_path());
 
-    let json_packages_object;
-    let lock_packages_object;
-    let rubigo_lock;
+    let mut new_manifest = manifest.clone();
+    let mut lock;
 
     if is_global {
-        let global_ps = &rubigo_json[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY];
-        if !global_ps.is_null() {
-            for i in 0..global_ps.len() {
-                if match global_ps[i].as_str() {
-                    Some(name) => name,
-                    None => continue,
-                } == pkg_import.as_str() {
-                    logger.fatal(format!("the package `{}` already exists in `rubigo.json` file", pkg_import));
-                    return;
-                }
-            }
+        if manifest.packages.global.contains(&pkg_import) {
+            return Err(format!("the package `{}` already exists in `rubigo.json` file", pkg_import))
+        }
+
+        if let Err(e) = vendor::check_offline(&array![], &array![pkg_import.clone()], false, ctx, logger) {
+            return Err(e.to_string())
         }
 
-        match go::get(pkg_import.as_str(), false) {
+        match go::get(pkg_import.as_str(), false, ctx.offline) {
             true => logger.verbose("Global package", &pkg_import),
             false => {
-                logger.fatal(format!("unable to get package `{}`", pkg_import));
-                return
+                return Err(format!("unable to get package `{}`", pkg_import))
             }
         }
 
-        rubigo_lock = rubigo_lock_future.wait().unwrap_or(object!{});
-
-        let mut global_pkgs = rubigo_json[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone();
-        if global_pkgs.is_null() {
-            global_pkgs = array![pkg_import.clone()];
-        } else {
-            let _ = global_pkgs.push(pkg_import.clone());
-        }
-
-        let mut lock_global_pkgs = rubigo_lock[json_helper::GLOBAL_KEY].clone();
-        if lock_global_pkgs.is_null() {
-            lock_global_pkgs = array![pkg_import];
-        } else {
-            let _ = lock_global_pkgs.push(pkg_import);
-        }
-
-        json_packages_object = object!{
-            json_helper::GIT_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::GIT_KEY].clone(),
-            json_helper::LOCAL_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone(),
-            json_helper::GLOBAL_KEY => global_pkgs
-        };
-
-        lock_packages_object = object!{
-            json_helper::GIT_KEY => rubigo_lock[json_helper::GIT_KEY].clone(),
-            json_helper::LOCAL_KEY => rubigo_lock[json_helper::LOCAL_KEY].clone(),
-            json_helper::GLOBAL_KEY => lock_global_pkgs
-        };
+        lock = helpers::unwrap_or_empty(lock_future.wait(), "rubigo.lock", logger);
+        new_manifest.packages.global.push(pkg_import.clone());
+        lock.global.push(pkg_import);
     } else if is_local {
-        let local_ps = &rubigo_json[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY];
-        if !local_ps.is_null() {
-            for i in 0..local_ps.len() {
-                if match local_ps[i].as_str() {
-                    Some(name) => name,
-                    None => continue,
-                } == pkg_import.as_str() {
-                    logger.fatal(format!("the package `{}` already exists in `rubigo.json` file", pkg_import));
-                    return;
-                }
-            }
+        if manifest.packages.local.contains(&pkg_import) {
+            return Err(format!("the package `{}` already exists in `rubigo.json` file", pkg_import))
         }
 
         let pkg_path = pkg_path_buf.as_path();
         if pkg_path.exists() {
-            logger.fatal(format!("the package `{}` already exists", pkg_import));
-            return
+            return Err(format!("the package `{}` already exists", pkg_import))
         }
 
         match create_dir_all(pkg_path) {
             Ok(_) => logger.verbose("Local package", &pkg_import),
             Err(e) => {
-                logger.fatal(e);
-                return
+                return Err(e.to_string())
             }
         }
 
-        rubigo_lock = rubigo_lock_future.wait().unwrap_or(object!{});
-
-        let mut local_pkgs = rubigo_json[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone();
-        if local_pkgs.is_null() {
-            local_pkgs = array![pkg_import.clone()];
-        } else {
-            let _ = local_pkgs.push(pkg_import.clone());
-        }
-
-        let mut lock_local_pkgs = rubigo_lock[json_helper::LOCAL_KEY].clone();
-        if lock_local_pkgs.is_null() {
-            lock_local_pkgs = array![pkg_import];
-        } else {
-            let _ = lock_local_pkgs.push(pkg_import);
+        lock = helpers::unwrap_or_empty(lock_future.wait(), "rubigo.lock", logger);
+        new_manifest.packages.local.push(pkg_import.clone());
+        lock.local.push(pkg_import);
+    } else {
+        if manifest.packages.git.iter().any(|pkg| pkg.import == pkg_import) {
+            return Err(format!("the package `{}` already exists in `rubigo.json` file", pkg_import))
         }
 
-        json_packages_object = object!{
-            json_helper::GIT_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::GIT_KEY].clone(),
-            json_helper::GLOBAL_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone(),
-            json_helper::LOCAL_KEY => local_pkgs
+        let mut pkg = GitPackage {
+            import: pkg_import.clone(),
+            repo: repo_url.map(|url| url.to_owned()),
+            vcs: vcs_name.map(|name| name.to_owned()),
+            ..GitPackage::default()
         };
 
-        lock_packages_object = object!{
-            json_helper::GIT_KEY => rubigo_lock[json_helper::GIT_KEY].clone(),
-            json_helper::GLOBAL_KEY => rubigo_lock[json_helper::GLOBAL_KEY].clone(),
-            json_helper::LOCAL_KEY => lock_local_pkgs
-        };
-    } else {
-        let git_ps = &rubigo_json[json_helper::PACKAGES_KEY][json_helper::GIT_KEY];
-        if !git_ps.is_null() {
-            for i in 0..git_ps.len() {
-                if match git_ps[i][json_helper::IMPORT_KEY].as_str() {
-                    Some(name) => name,
-                    None => continue,
-                } == pkg_import.as_str() {
-                    logger.fatal(format!("the package `{}` already exists in `rubigo.json` file", pkg_import));
-                    return;
-                }
-            }
+        // The package is replaced in `vendor` directory, so only the cache could satisfy it offline
+        if let Err(e) = vendor::check_offline(&array![pkg.to_json()], &array![], true, ctx, logger) {
+            return Err(e.to_string())
         }
 
-        let mut pkg_json = object!{
-            json_helper::IMPORT_KEY => pkg_import.clone()
-        };
-
-        let (pkg_import_url, modified_pkg_path) = helpers::modify_golang_org(pkg_import.as_str());
-        let modified_pkg_import = if modified_pkg_path.is_some() {
-            modified_pkg_path.unwrap()
-        } else {
-            pkg_import.clone()
+        let (pkg_import_url, location_path_buf, kind) = match vendor::locate_package(pkg_import.as_str(), repo_url, vcs_name, ctx) {
+            Ok(location) => location,
+            Err(e) => {
+                return Err(e.to_string())
+            },
         };
-        pkg_path_buf = helpers::get_path_from_url(&modified_pkg_import);
+        pkg_path_buf = location_path_buf;
         let pkg_path = pkg_path_buf.as_path();
         if pkg_path.exists() {
             logger.error(format!("the package `{}` already exists in `vendor` directory", pkg_import));
             match remove_dir_all(pkg_path) {
                 Ok(_) => logger.verbose("Delete directory", &pkg_import),
                 Err(e) => {
-                    logger.fatal(e);
-                    return
+                    return Err(e.to_string())
                 },
             }
         }
         match create_dir_all(pkg_path) {
             Ok(_) => logger.verbose("Create directory", &pkg_import),
             Err(e) => {
-                logger.fatal(e);
-                return
+                return Err(e.to_string())
             },
         }
 
-        let repo = match Repository::clone(match repo_url {
-            Some(url) => {
-                pkg_json[json_helper::REPO_KEY] = url.into();
-                url
-            },
-            None => &pkg_import_url,
-        }, pkg_path) {
+        let clone_url = config::clone_url(&ctx.config, pkg_import_url.as_str());
+        let clone_task = logger.task(pkg_import.as_str());
+        let repo = match vcs::clone(kind, clone_url.as_str(), pkg_path, ctx, logger) {
             Ok(repo) => {
                 logger.verbose("Clone repository", &pkg_import);
-                repo
+                vcs::for_import(repo, pkg_import.as_str())
             },
             Err(e) => {
                 let _ = remove_dir_all(pkg_path);
-                logger.fatal(e);
-                return
+                return Err(e.to_string())
             },
         };
 
-        let mut lock_pkg_json = pkg_json.clone();
+        // The progress is cleared before prompting the version
+        drop(clone_task);
+
+        let mut locked_pkg = LockedPackage {
+            import: pkg.import.clone(),
+            repo: pkg.repo.clone(),
+            vcs: pkg.vcs.clone(),
+            ..LockedPackage::default()
+        };
+        if clone_url != pkg_import_url {
+            locked_pkg.url = Some(clone_url);
+        }
 
         let version;
         if !no_prompt {
-            let (ver, rule) = match helpers::version_prompt(&repo) {
+            let (ver, rule) = match helpers::version_prompt(repo.as_ref()) {
                 Some(ver) => ver,
                 None => {
                     let _ = remove_dir_all(pkg_path);
-                    logger.fatal("unable to get latest commit of package");
-                    return
+                    return Err(String::from("unable to get latest commit of package"))
                 },
             };
             version = ver;
-            pkg_json[json_helper::VERSION_KEY] = rule.into();
+            pkg.version = rule;
         } else {
-            version = match git_helper::get_latest_commit(&repo) {
+            version = match repo.latest_commit() {
                 Some(ver) => ver,
                 None => {
                     let _ = remove_dir_all(pkg_path);
-                    logger.fatal("unable to get latest commit of package");
-                    return
+                    return Err(String::from("unable to get latest commit of package"))
                 },
             };
 
-            pkg_json[json_helper::VERSION_KEY] = version.clone().into();
+            pkg.version = version.clone();
         }
 
-        let version_object = match git_helper::get_revision_object(&repo, pkg_import.clone(), version, true, logger) {
-            Some(tup) => {
-                lock_pkg_json[json_helper::VERSION_KEY] = tup.1.into();
-                tup.0
+        let commit = match vcs::resolve_or_latest(repo.as_ref(), pkg_import.as_str(), version, logger) {
+            Some((commit, version)) => {
+                locked_pkg.version = version;
+                commit
             },
             None => {
                 let _ = remove_dir_all(pkg_path);
-                logger.fatal("unable to parse the version of package");
-                return
+                return Err(String::from("unable to parse the version of package"))
             }
         };
+        locked_pkg.commit = Some(commit.clone());
 
-        match repo.set_head_detached(version_object.id()) {
-            Ok(_) => (),
+        let checkout_task = logger.task(pkg_import.as_str());
+        match repo.checkout(commit.as_str()) {
+            Ok(_) => drop(checkout_task),
             Err(e) => {
                 let _ = remove_dir_all(pkg_path);
-                logger.fatal(e);
-                return
+                return Err(e.to_string())
             },
         }
 
-        match repo.reset(&version_object, ResetType::Hard, None){
-            Ok(_) => (),
+        match integrity::hash_dir(pkg_path) {
+            Ok(hash) => locked_pkg.hash = Some(hash),
             Err(e) => {
                 let _ = remove_dir_all(pkg_path);
-                logger.fatal(e);
-                return
+                return Err(format!("unable to hash package: {}", e))
             },
         }
+        locked_pkg.sum = repo.sum();
 
-        let mut git_pkgs = rubigo_json[json_helper::PACKAGES_KEY][json_helper::GIT_KEY].clone();
-        if git_pkgs.is_null() {
-            git_pkgs = array![pkg_json];
-        } else {
-            let _ = git_pkgs.push(pkg_json);
-        }
-
-        rubigo_lock = rubigo_lock_future.wait().unwrap_or(object!{});
-        let mut lock_git_pkgs = rubigo_lock[json_helper::GIT_KEY].clone();
-        if lock_git_pkgs.is_null() {
-            lock_git_pkgs = array![lock_pkg_json];
-        } else {
-            let _ = lock_git_pkgs.push(lock_pkg_json);
-        }
-
-        json_packages_object = object!{
-            json_helper::GIT_KEY => git_pkgs,
-            json_helper::LOCAL_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone(),
-            json_helper::GLOBAL_KEY => rubigo_json[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone()
-        };
+        new_manifest.packages.git.push(pkg);
 
-        lock_packages_object = object!{
-            json_helper::GIT_KEY => lock_git_pkgs,
-            json_helper::LOCAL_KEY => rubigo_lock[json_helper::LOCAL_KEY].clone(),
-            json_helper::GLOBAL_KEY => rubigo_lock[json_helper::GLOBAL_KEY].clone()
+        lock = helpers::unwrap_or_empty(lock_future.wait(), "rubigo.lock", logger);
+        let transitive_pkgs = match vendor::resolve_git_packages(&array![locked_pkg.pinned().to_json()], &lock.git_to_json(), ctx, logger) {
+            Some(resolved) => json_helper::remove_package_from_array(pkg_import.as_str(), &resolved, false),
+            None => {
+                let _ = remove_dir_all(pkg_path);
+                return Err(String::from("unable to resolve the dependencies of package"))
+            },
         };
+        lock.git.push(locked_pkg);
+        lock.git.extend(manifest::locked_packages(&vendor::install_git_packages(&transitive_pkgs, "Install dependency", false, false, false, ctx, logger)));
     }
 
-    match json_helper::write("rubigo.json", "", Some(object!{
-        json_helper::INFO_KEY => rubigo_json[json_helper::INFO_KEY].clone(),
-        json_helper::PACKAGES_KEY => json_packages_object
-    })) {
+    match json_helper::write("rubigo.json", "", Some(new_manifest.to_json())) {
         Ok(_) => logger.verbose("Update file", "rubigo.json"),
         Err(e) => {
             if !is_global {
                 let _ = remove_dir_all(pkg_path_buf.as_path());
             }
-            logger.fatal(format!("unable to write to `rubigo.json`: {}", e));
-            return
+            return Err(format!("unable to write to `rubigo.json`: {}", e))
         },
     }
 
-    match json_helper::write("rubigo.lock", "", Some(lock_packages_object)) {
+    match json_helper::write("rubigo.lock", "", Some(lock.to_json())) {
         Ok(_) => logger.verbose("Update file", "rubigo.lock"),
         Err(e) => {
-            let _ = json_helper::write("rubigo.json", "", Some(rubigo_json));
+            let _ = json_helper::write("rubigo.json", "", Some(manifest.to_json()));
             let _ = remove_dir_all(pkg_path_buf.as_path());
-            logger.fatal(format!("unable to write to `rubigo.lock`: {}", e))
+            return Err(format!("unable to write to `rubigo.lock`: {}", e))
         },
     }
+    Ok(())
 }
 
 pub fn remove(package_dir: &str, logger: Logger) {
-    let json_content = match json_helper::read(Path::new("rubigo.json")) {
+    let json_content = match json_helper::read_manifest(Path::new("rubigo.json")) {
         Ok(content) => content,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.json`: {}", e));
@@ -342,7 +261,7 @@ pub fn remove(package_dir: &str, logger: Logger) {
         }
     };
 
-    let lock_content = match json_helper::read(Path::new("rubigo.lock")) {
+    let lock_content = match json_helper::read_lock(Path::new("rubigo.lock")) {
         Ok(content) => content,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
@@ -351,12 +270,22 @@ pub fn remove(package_dir: &str, logger: Logger) {
     };
 
     let new_json_git = json_helper::remove_package_from_array(package_dir, &json_content[json_helper::PACKAGES_KEY][json_helper::GIT_KEY], false);
-    let new_lock_git = json_helper::remove_package_from_array(package_dir, &lock_content[json_helper::GIT_KEY], false);
+    let mut new_lock_git = json_helper::remove_package_from_array(package_dir, &lock_content[json_helper::GIT_KEY], false);
     let new_json_local = json_helper::remove_package_from_array(package_dir, &json_content[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY], true);
     let new_lock_local = json_helper::remove_package_from_array(package_dir, &lock_content[json_helper::LOCAL_KEY], true);
     let new_json_global = json_helper::remove_package_from_array(package_dir, &json_content[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY], true);
     let new_lock_global = json_helper::remove_package_from_array(package_dir, &lock_content[json_helper::GLOBAL_KEY], true);
 
+    // The dependencies which were only required by the removed package are removed along with it
+    let vendor_path = Path::new(vendor::VENDOR_DIR);
+    let mut removed_dirs = vec![package_dir.to_owned()];
+    if new_lock_git.len() < lock_content[json_helper::GIT_KEY].len() {
+        for orphan in find_orphans(&new_json_git, &new_lock_git, vendor_path) {
+            new_lock_git = json_helper::remove_package_from_array(orphan.as_str(), &new_lock_git, false);
+            removed_dirs.push(helpers::strip_url_scheme(orphan.as_str()));
+        }
+    }
+
     match json_helper::write("rubigo.json", "", Some(object!{
         json_helper::INFO_KEY => json_content[json_helper::INFO_KEY].clone(),
         json_helper::PACKAGES_KEY => object!{
@@ -388,10 +317,8 @@ pub fn remove(package_dir: &str, logger: Logger) {
         },
     }
 
-    let pkg_path_buf = helpers::get_path_from_url(package_dir);
-    let pkg_path = pkg_path_buf.as_path();
-    if pkg_path.exists() {
-        if !helpers::remove_package(package_dir, logger) {
+    for dir in &removed_dirs {
+        if helpers::get_path_from_url(dir, vendor_path).exists() && !helpers::remove_package(dir, vendor_path, logger) {
             match json_helper::write("rubigo.json", "", Some(json_content)) {
                 Ok(_) => logger.verbose("Revert file", "rubigo.json"),
                 Err(e) => logger.error(format!("unable to revert `rubigo.json`: {}", e)),
@@ -405,140 +332,256 @@ pub fn remove(package_dir: &str, logger: Logger) {
     }
 }
 
-pub fn update(package_url: Option<&str>, should_clean: bool, logger: Logger) {
-    let json_content = match json_helper::read(Path::new("rubigo.json")) {
-        Ok(content) => content,
+// The transitive packages of the lock which are neither required by the dependency files of a required package nor by their `parent`
+fn find_orphans(json_git: &JsonValue, lock_git: &JsonValue, vendor_path: &Path) -> Vec<String> {
+    let mut required: Vec<String> = json_git.members().filter_map(|pkg| pkg[json_helper::IMPORT_KEY].as_str()).map(helpers::strip_url_scheme).collect();
+    let mut i = 0;
+    while i < required.len() {
+        let import = required[i].clone();
+        i += 1;
+        let dependencies: Vec<String> = importer::dependencies(helpers::get_path_from_url(import.as_str(), vendor_path).as_path()).unwrap_or_default()
+            .iter().map(|dep| helpers::strip_url_scheme(dep.import.as_str())).collect();
+        for pkg in lock_git.members() {
+            let pkg_import = match pkg[json_helper::IMPORT_KEY].as_str() {
+                Some(import_str) => helpers::strip_url_scheme(import_str),
+                None => continue,
+            };
+            let is_required = dependencies.contains(&pkg_import) || pkg[json_helper::PARENT_KEY].as_str().map(helpers::strip_url_scheme).as_ref() == Some(&import);
+            if is_required && !required.contains(&pkg_import) {
+                required.push(pkg_import);
+            }
+        }
+    }
+    // The packages without `parent` are only added by the user, so they are kept
+    lock_git.members().filter(|pkg| !pkg[json_helper::PARENT_KEY].is_null())
+        .filter_map(|pkg| pkg[json_helper::IMPORT_KEY].as_str())
+        .filter(|import| !required.contains(&helpers::strip_url_scheme(import)))
+        .map(|import| import.to_owned())
+        .collect()
+}
+
+pub fn update(package_url: Option<&str>, should_clean: bool, ctx: &Context, logger: Logger) {
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.json`: {}", e));
             return
         }
     };
 
-    if package_url.is_some() {
-        let mut git_pkgs = json_content[json_helper::PACKAGES_KEY][json_helper::GIT_KEY].clone();
-        let mut pkg = None;
-        if !git_pkgs.is_null() {
-            for i in 0..git_pkgs.len() {
-                if match git_pkgs[i][json_helper::IMPORT_KEY].as_str() {
-                    Some(name) => name,
-                    None => continue,
-                } == package_url.unwrap() {
-                    pkg = Some(git_pkgs.array_remove(i));
-                    break;
+    if let Some(package_url) = package_url {
+        let mut git_pkgs = manifest.packages.git.clone();
+        let pkg = git_pkgs.iter().position(|pkg| pkg.import == package_url).map(|index| git_pkgs.remove(index));
+
+        let old_lock = helpers::unwrap_or_empty(Lock::load(Path::new("rubigo.lock")), "rubigo.lock", logger);
+        // Every entry of the package is dropped, even if its import has a different url scheme, so it is locked only once
+        let updated_import = helpers::strip_url_scheme(package_url);
+        let mut lock = Lock {
+            git: if old_lock.git.is_empty() {
+                git_pkgs.into_iter().map(|pkg| LockedPackage {
+                    import: pkg.import,
+                    repo: pkg.repo,
+                    vcs: pkg.vcs,
+                    version: pkg.version,
+                    exclude: pkg.exclude,
+                    ..LockedPackage::default()
+                }).collect()
+            } else {
+                old_lock.git.into_iter().filter(|locked_pkg| helpers::strip_url_scheme(locked_pkg.import.as_str()) != updated_import).collect()
+            },
+            local: manifest.packages.local.clone(),
+            global: manifest.packages.global.clone(),
+        };
+
+        let pkg = match pkg {
+            Some(pkg) => pkg,
+            None => {
+                if lock.global.is_empty() {
+                    logger.fatal(format!("the package `{0}` did not find in `rubigo.json` file", package_url));
+                    return
                 }
-            }
-        }
-        if pkg.is_none() {
-            let mut global_pkgs = json_content[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone();
-            if global_pkgs.is_null() {
-                logger.fatal(format!("the package `{0}` did not find in `rubigo.json` file", package_url.unwrap()));
-                return
-            }
-            let mut global_pkg = None;
-            for i in 0..global_pkgs.len() {
-                if match global_pkgs[i].as_str() {
-                    Some(name) => name,
-                    None => continue,
-                } == package_url.unwrap() {
-                    global_pkg = Some(match global_pkgs.array_remove(i).as_str() {
-                        Some(name) => name.to_owned(),
-                        None => {
-                            logger.fatal(format!("the package `{0}` is not installed, it could be installed using `rubigo get {0}`", package_url.unwrap()));
-                            return
-                        }
-                    });
-                    break;
+                let g_pkg = match lock.global.iter().position(|name| name == package_url) {
+                    Some(index) => lock.global.remove(index),
+                    None => {
+                        logger.fatal(format!("the package `{0}` is not installed, it could be installed using `rubigo get {0}`", package_url));
+                        return
+                    },
+                };
+
+                match go::get(&g_pkg, true, ctx.offline) {
+                    true => {
+                        logger.verbose("Global package", &g_pkg);
+                        lock.global.push(g_pkg);
+                    },
+                    false => {
+                        logger.fatal(format!("unable to update global package of `{}`", &g_pkg));
+                        return
+                    },
                 }
-            }
-
-            if global_pkg.is_none() {
-                logger.fatal(format!("the package `{0}` is not installed, it could be installed using `rubigo get {0}`", package_url.unwrap()));
-                return
-            }
 
-            let g_pkg = global_pkg.unwrap();
-            match go::get(&g_pkg, true) {
-                true => {
-                    logger.verbose("Global package", &g_pkg);
-                    let _ = global_pkgs.push(g_pkg);
-                },
-                false => {
-                    logger.fatal(format!("unable to update global package of `{}`", &g_pkg));
-                    return
-                },
-            }
+                match json_helper::write("rubigo.lock", "", Some(lock.to_json())) {
+                    Ok(_) => logger.verbose("Update file", "rubigo.lock"),
+                    Err(e) => logger.error(e),
+                }
 
-            match json_helper::write("rubigo.lock", "", Some(object!{
-                json_helper::GIT_KEY => json_content[json_helper::PACKAGES_KEY][json_helper::GIT_KEY].clone(),
-                json_helper::LOCAL_KEY => json_content[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone(),
-                json_helper::GLOBAL_KEY => global_pkgs
-            })) {
-                Ok(_) => logger.verbose("Update file", "rubigo.lock"),
-                Err(e) => logger.error(e),
-            }
+                return
+            },
+        };
 
+        if let Err(e) = vendor::check_offline(&array![pkg.to_json()], &array![], should_clean, ctx, logger) {
+            logger.fatal(e);
             return
         }
 
+        // Only the package is copied, it replaces the one of `vendor` directory along with `rubigo.lock` if it is updated
+        let pkg_import = helpers::strip_url_scheme(pkg.import.as_str());
+        let staging = match Staging::begin(vec![pkg_import.clone()], ctx.vendor_dir.as_path(), logger) {
+            Ok(staging) => staging,
+            Err(e) => {
+                logger.fatal(e);
+                return
+            },
+        };
+        let mut staged_ctx = ctx.clone();
+        staged_ctx.vendor_dir = staging.vendor_dir();
+
         let (tx, rx) = channel();
+        let pkg_json = pkg.to_json();
+        let c_ctx = staged_ctx.clone();
         thread::spawn(move|| {
-            vendor::update_package(pkg.unwrap(), should_clean, false, tx, logger);
+            vendor::update_package(pkg_json, should_clean, false, false, tx, &c_ctx, logger);
         });
 
-        match rx.recv() {
-            Ok(p) => {
-                logger.verbose("Update package", match p[json_helper::IMPORT_KEY].as_str() {
-                    Some(import_str) => import_str,
-                    None => "unknown",
-                });
-                let _ = git_pkgs.push(p);
+        let updated_pkg = match rx.recv() {
+            Ok(ref update) if update.error.is_some() => {
+                staging.rollback();
+                logger.fatal(format!("unable to update `{}`, `vendor` directory and `rubigo.lock` have not been changed", pkg_import));
+                return
             },
-            Err(e) => logger.fatal(e),
+            Ok(update) => match LockedPackage::from_json(&update.package) {
+                Some(updated_pkg) => {
+                    logger.verbose("Update package", updated_pkg.import.as_str());
+                    updated_pkg
+                },
+                None => {
+                    staging.rollback();
+                    logger.fatal(format!("unable to update `{}`, `vendor` directory and `rubigo.lock` have not been changed", pkg_import));
+                    return
+                },
+            },
+            Err(e) => {
+                staging.rollback();
+                logger.fatal(e);
+                return
+            },
+        };
+
+        // The dependencies of the new version are resolved like `get` sub-command, the locked versions of the other packages are preferred
+        let transitive_pkgs = match vendor::resolve_git_packages(&array![updated_pkg.pinned().to_json()], &lock.git_to_json(), &staged_ctx, logger) {
+            Some(resolved) => json_helper::remove_package_from_array(updated_pkg.import.as_str(), &resolved, false),
+            None => {
+                staging.rollback();
+                logger.fatal(format!("unable to resolve the dependencies of `{}`, `vendor` directory and `rubigo.lock` have not been changed", pkg_import));
+                return
+            },
+        };
+        let mut changed_pkgs = array![];
+        for transitive_pkg in transitive_pkgs.members() {
+            let is_locked = GitPackage::from_json(transitive_pkg).map_or(false, |resolved_pkg| {
+                lock.git.iter().any(|locked_pkg| locked_pkg.import == resolved_pkg.import && vendor::is_locked_version(locked_pkg.version.as_str(), resolved_pkg.version.as_str()))
+            });
+            if !is_locked {
+                let _ = changed_pkgs.push(transitive_pkg.clone());
+            }
         }
+        let (installed_pkgs, failed) = vendor::try_install_git_packages(&changed_pkgs, "Install dependency", should_clean, false, false, &staged_ctx, logger);
+        if failed > 0 {
+            staging.rollback();
+            logger.fatal(format!("{} dependency(ies) of `{}` could not be installed, `vendor` directory and `rubigo.lock` have not been changed", failed, pkg_import));
+            return
+        }
+        for installed_pkg in manifest::locked_packages(&installed_pkgs) {
+            lock.replace_package(installed_pkg);
+        }
+        lock.replace_package(updated_pkg);
 
-        match json_helper::write("rubigo.lock", "", Some(object!{
-            json_helper::GIT_KEY => git_pkgs,
-            json_helper::LOCAL_KEY => json_content[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone(),
-            json_helper::GLOBAL_KEY => json_content[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone()
-        })) {
-            Ok(_) => logger.verbose("Update file", "rubigo.lock"),
-            Err(e) => logger.error(e),
+        if let Err(e) = staging.commit(lock.to_json(), logger) {
+            logger.fatal(e);
         }
 
         return;
     }
 
-    let pool = CpuPool::new(2);
+    let git_pkgs = manifest.packages.git_to_json();
+    if let Err(e) = vendor::check_offline(&git_pkgs, &manifest::strings_to_json(&manifest.packages.global), should_clean, ctx, logger) {
+        logger.fatal(e);
+        return
+    }
 
-    let old_lock_future = pool.spawn_fn(|| {
-        match json_helper::read(Path::new("rubigo.lock")) {
-            Ok(content_json) => Ok(content_json),
-            Err(e) => Err(e),
+    // Nothing is changed in `vendor` directory and `rubigo.lock` unless every package is updated
+    let old_lock = helpers::unwrap_or_empty(Lock::load(Path::new("rubigo.lock")), "rubigo.lock", logger);
+    let mut imports = staging::lock_imports(&manifest.packages.to_json());
+    for import in staging::lock_imports(&old_lock.to_json()) {
+        if !imports.contains(&import) {
+            imports.push(import);
         }
-    });
+    }
+    let staging = match Staging::begin(imports, ctx.vendor_dir.as_path(), logger) {
+        Ok(staging) => staging,
+        Err(e) => {
+            logger.fatal(e);
+            return
+        },
+    };
+    let mut staged_ctx = ctx.clone();
+    staged_ctx.vendor_dir = staging.vendor_dir();
 
-    let c_json = json_content[json_helper::PACKAGES_KEY][json_helper::LOCAL_KEY].clone();
+    let pool = CpuPool::new(2);
+
+    let c_local = manifest::strings_to_json(&manifest.packages.local);
+    let vendor_path = staged_ctx.vendor_dir.clone();
     let local_packages = pool.spawn_fn(move || {
-        Ok::<JsonValue, ()>(vendor::install_local_packages(&c_json, logger))
+        Ok::<JsonValue, ()>(vendor::install_local_packages(&c_local, vendor_path.as_path(), logger))
     });
 
-    let c_json2 = json_content[json_helper::PACKAGES_KEY][json_helper::GLOBAL_KEY].clone();
+    let c_global = manifest::strings_to_json(&manifest.packages.global);
+    let c_ctx = ctx.clone();
     let global_packages = pool.spawn_fn(move || {
-        Ok::<JsonValue, ()>(vendor::install_global_packages(&c_json2, true, logger))
+        Ok::<JsonValue, ()>(vendor::install_global_packages(&c_global, true, &c_ctx, logger))
     });
 
-    let git_packages = vendor::install_git_packages(&json_content[json_helper::PACKAGES_KEY][json_helper::GIT_KEY], "Update package", should_clean, false, logger);
+    let resolved_packages = match vendor::resolve_git_packages(&git_pkgs, &array![], &staged_ctx, logger) {
+        Some(resolved) => resolved,
+        None => {
+            staging.rollback();
+            logger.fatal("unable to resolve the versions of packages, `vendor` directory and `rubigo.lock` have not been changed");
+            return
+        },
+    };
+    let (git_packages, failed) = vendor::try_install_git_packages(&resolved_packages, "Update package", should_clean, false, false, &staged_ctx, logger);
+    let local_packages = manifest::json_to_strings(&local_packages.wait().unwrap_or(array![]));
+    let global_packages = manifest::json_to_strings(&global_packages.wait().unwrap_or(array![]));
+    let failed = failed + count_missing(&manifest.packages.local, &local_packages) + count_missing(&manifest.packages.global, &global_packages);
+    if failed > 0 {
+        staging.rollback();
+        logger.fatal(format!("{} package(s) could not be updated, `vendor` directory and `rubigo.lock` have not been changed", failed));
+        return
+    }
 
-    let new_lock = object!{
-        json_helper::GIT_KEY => git_packages,
-        json_helper::LOCAL_KEY => local_packages.wait().unwrap_or(array![]),
-        json_helper::GLOBAL_KEY => global_packages.wait().unwrap_or(array![])
+    let new_lock = Lock {
+        git: manifest::locked_packages(&git_packages),
+        local: local_packages,
+        global: global_packages,
     };
 
-    helpers::remove_diff_packages(&old_lock_future.wait().unwrap_or(object![]), &new_lock, logger);
+    helpers::remove_diff_packages(&old_lock.to_json(), &new_lock.to_json(), staged_ctx.vendor_dir.as_path(), logger);
 
-    match json_helper::write("rubigo.lock", "", Some(new_lock)) {
-        Ok(_) => logger.verbose("Update file", "rubigo.lock"),
-        Err(e) => logger.error(e),
+    if let Err(e) = staging.commit(new_lock.to_json(), logger) {
+        logger.fatal(e);
     }
 }
+
+// The number of packages of `rubigo.json` which are not installed
+fn count_missing(packages: &[String], installed: &[String]) -> usize {
+    packages.iter().filter(|pkg| !installed.contains(pkg)).count()
+}
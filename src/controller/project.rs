@@ -1,3 +1,4 @@
+use inner::context::Context;
 use inner::logger::Logger;
 use std::path::Path;
 use std::fs::{File, create_dir, create_dir_all, remove_dir_all, remove_file};
@@ -5,7 +6,9 @@ use std::env::current_dir;
 use std::fmt::Display;
 use git2::Repository;
 use std::io::Write;
-use inner::{vendor, json_helper, helpers};
+use inner::{vendor, json_helper, helpers, importer};
+use inner::manifest::{self, Manifest, Packages, GitPackage, Lock};
+use controller::import;
 use futures::Future;
 use futures_cpupool::CpuPool;
 use std::thread;
@@ -80,7 +83,7 @@ pub fn new(name: &str, is_lib: bool, logger: &Logger) {
     }
 }
 
-pub fn init(logger: Logger) {
+pub fn init(ctx: &Context, logger: Logger) {
     fn delete_init_project<T: Display>(err: T, path: &Path, logger: &Logger) {
         match remove_file(path) {
             Ok(_) => logger.verbose("Delete file", "rubigo.json"),
@@ -102,6 +105,12 @@ pub fn init(logger: Logger) {
             Err(e) => delete_init_project(e, json_path, &logger),
         }
     }
+    if let Some(tool) = importer::detect(Path::new(".")) {
+        logger.verbose("Import", tool.manifest_file());
+        import::import_tool(tool, false, ctx, logger);
+        return
+    }
+
     let parent_name = helpers::get_current_dir();
     let vendor_path = Path::new(vendor::VENDOR_DIR);
     if !vendor_path.exists() {
@@ -116,13 +125,13 @@ pub fn init(logger: Logger) {
         }
     } else {
         logger.verbose("Synchronize", "vendor directory");
-        let git_packages = vendor::find_packages(logger);
+        let git_packages = vendor::find_packages(ctx, logger);
         match json_helper::write(json_path, "", Some(object!{
             json_helper::INFO_KEY => object!{
                 json_helper::NAME_KEY => parent_name.as_str()
             },
             json_helper::PACKAGES_KEY => object!{
-                json_helper::GIT_KEY => git_packages.clone(),
+                json_helper::GIT_KEY => json_helper::strip_lock_keys(&git_packages),
                 json_helper::LOCAL_KEY => array![],
                 json_helper::GLOBAL_KEY => array![]
             }
@@ -148,13 +157,13 @@ pub fn init(logger: Logger) {
     }
 }
 
-pub fn reset(no_prompt: bool, logger: Logger) {
+pub fn reset(no_prompt: bool, ctx: &Context, logger: Logger) {
     if no_prompt {
-        inner_reset(logger);
+        inner_reset(ctx, logger);
     } else {
         match helpers::confirmation_prompt("This sub command might cause unexpected changes in `rubigo.json` and `rubigo.lock` files.\nDo you want to continue? [Y/n]") {
             Ok(accepted) => if accepted {
-                inner_reset(logger);
+                inner_reset(ctx, logger);
             } else {
                 logger.error("aborted");
             },
@@ -165,63 +174,32 @@ pub fn reset(no_prompt: bool, logger: Logger) {
         }
     }
 
-    fn inner_reset(logger: Logger) {
+    fn inner_reset(ctx: &Context, logger: Logger) {
         if !Path::new(vendor::VENDOR_DIR).is_dir() {
             logger.fatal("vendor directory not found.");
             return
         }
 
         let pool = CpuPool::new(2);
-        let rubigo_json_future = pool.spawn_fn(|| {
-            match json_helper::read(Path::new("rubigo.json")) {
-                Ok(content_json) => Ok(content_json),
-                Err(e) => Err(e),
-            }
-        });
-        let rubigo_lock_future = pool.spawn_fn(|| {
-            match json_helper::read(Path::new("rubigo.lock")) {
-                Ok(content_json) => Ok(content_json),
-                Err(e) => Err(e),
-            }
-        });
+        let manifest_future = pool.spawn_fn(|| Manifest::load(Path::new("rubigo.json")));
+        let lock_future = pool.spawn_fn(|| Lock::load(Path::new("rubigo.lock")));
 
         logger.verbose("Synchronize", "vendor directory");
-        let git_packages = vendor::find_packages(logger);
+        let git_packages = vendor::find_packages(ctx, logger);
 
-        let rubigo_json = rubigo_json_future.wait().unwrap_or(object!{});
-        let rubigo_lock = rubigo_lock_future.wait().unwrap_or(object!{});
-        let mut global_packages = rubigo_lock[json_helper::GLOBAL_KEY].clone();
-        if global_packages.is_null() {
-            global_packages = array![];
-        }
-        let local_packages = &rubigo_lock[json_helper::LOCAL_KEY];
-        let mut local_packages_result = array![];
-        if !local_packages.is_null() {
-            for i in 0..local_packages.len() {
-                let local_pkg = local_packages[i].clone();
-                if Path::new(vendor::VENDOR_DIR).join(match local_pkg.as_str() {
-                    Some(val_str) => val_str,
-                    None => continue,
-                }).is_dir() {
-                    match local_packages_result.push(local_pkg) {
-                        _ => (),
-                    }
-                }
-            }
-        }
+        let manifest = helpers::unwrap_or_empty(manifest_future.wait(), "rubigo.json", logger);
+        let lock = helpers::unwrap_or_empty(lock_future.wait(), "rubigo.lock", logger);
+        let local_packages: Vec<String> = lock.local.iter().filter(|local_pkg| Path::new(vendor::VENDOR_DIR).join(local_pkg.as_str()).is_dir()).cloned().collect();
 
-        let mut info_obj = rubigo_json[json_helper::INFO_KEY].clone();
-        if info_obj.is_null() {
-            info_obj = object!{};
-        }
-        match json_helper::write(Path::new("rubigo.json"), "", Some(object!{
-            json_helper::INFO_KEY => info_obj,
-            json_helper::PACKAGES_KEY => object!{
-                json_helper::GIT_KEY => git_packages.clone(),
-                json_helper::LOCAL_KEY => local_packages_result.clone(),
-                json_helper::GLOBAL_KEY => global_packages.clone()
-            }
-        })) {
+        let new_manifest = Manifest {
+            info: manifest.info.clone(),
+            packages: Packages {
+                git: git_packages.members().filter_map(GitPackage::from_json).collect(),
+                local: local_packages.clone(),
+                global: lock.global.clone(),
+            },
+        };
+        match json_helper::write(Path::new("rubigo.json"), "", Some(new_manifest.to_json())) {
             Ok(_) => logger.verbose("Replace file", "rubigo.json"),
             Err(e) => {
                 logger.fatal(format!("unable to write to `rubigo.json`: {}", e));
@@ -229,14 +207,15 @@ pub fn reset(no_prompt: bool, logger: Logger) {
             },
         }
 
-        match json_helper::write(Path::new("rubigo.lock"), "", Some(object!{
-            json_helper::GIT_KEY => git_packages,
-            json_helper::LOCAL_KEY => local_packages_result,
-            json_helper::GLOBAL_KEY => global_packages
-        })) {
+        let new_lock = Lock {
+            git: manifest::locked_packages(&git_packages),
+            local: local_packages,
+            global: lock.global,
+        };
+        match json_helper::write(Path::new("rubigo.lock"), "", Some(new_lock.to_json())) {
             Ok(_) => logger.verbose("Replace file", "rubigo.lock"),
             Err(e) => {
-                match json_helper::write("rubigo.json", "", Some(rubigo_json)) {
+                match json_helper::write("rubigo.json", "", Some(manifest.to_json())) {
                     Ok(_) => logger.verbose("Revert file", "rubigo.json"),
                     Err(e) => logger.error(format!("unable to revert `rubigo.json`: {}", e)),
                 }
@@ -246,8 +225,8 @@ pub fn reset(no_prompt: bool, logger: Logger) {
     }
 }
 
-pub fn apply(should_clean: bool, logger: Logger) {
-    let lock_content = match json_helper::read(Path::new("rubigo.lock")) {
+pub fn apply(should_clean: bool, strip_vcs: bool, ctx: &Context, logger: Logger) {
+    let lock_content = match json_helper::read_lock(Path::new("rubigo.lock")) {
         Ok(content) => content,
         Err(e) => {
             logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
@@ -255,17 +234,24 @@ pub fn apply(should_clean: bool, logger: Logger) {
         }
     };
 
+    if let Err(e) = vendor::check_offline(&lock_content[json_helper::GIT_KEY], &lock_content[json_helper::GLOBAL_KEY], should_clean, ctx, logger) {
+        logger.fatal(e);
+        return
+    }
+
     let c_lock = lock_content.clone();
+    let vendor_path = ctx.vendor_dir.clone();
     let local_thread = thread::spawn(move || {
-        let _ = vendor::install_local_packages(&c_lock[json_helper::LOCAL_KEY], logger);
+        let _ = vendor::install_local_packages(&c_lock[json_helper::LOCAL_KEY], vendor_path.as_path(), logger);
     });
 
     let c_lock2 = lock_content.clone();
+    let c_ctx = ctx.clone();
     let global_thread = thread::spawn(move || {
-        let _ = vendor::install_global_packages(&c_lock2[json_helper::GLOBAL_KEY], false, logger);
+        let _ = vendor::install_global_packages(&c_lock2[json_helper::GLOBAL_KEY], false, &c_ctx, logger);
     });
 
-    let _ = vendor::install_git_packages(&lock_content[json_helper::GIT_KEY], "Check package", should_clean, true, logger);
+    let _ = vendor::install_git_packages(&lock_content[json_helper::GIT_KEY], "Check package", should_clean, true, strip_vcs, ctx, logger);
 
     match local_thread.join() {
         Ok(_) => (),
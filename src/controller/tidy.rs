@@ -0,0 +1,81 @@
+use inner::context::Context;
+use inner::logger::Logger;
+use inner::{imports, helpers};
+use inner::manifest::Manifest;
+use inner::list_helper::{print_header, print_str_packages};
+use controller::package;
+use std::collections::BTreeSet;
+use std::path::Path;
+use json::JsonValue;
+
+pub fn tidy(should_add: bool, should_remove: bool, no_prompt: bool, ctx: &Context, logger: Logger) {
+    let manifest = match Manifest::load(Path::new("rubigo.json")) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.json`: {}", e));
+            return
+        },
+    };
+
+    let project_imports = match imports::scan_dir(Path::new(".")) {
+        Ok(project_imports) => project_imports,
+        Err(e) => {
+            logger.fatal(format!("unable to scan the imports of project: {}", e));
+            return
+        },
+    };
+
+    let mut packages: Vec<&str> = manifest.packages.git.iter().map(|pkg| pkg.import.as_str()).collect();
+    packages.extend(manifest.packages.local.iter().map(|pkg| pkg.as_str()));
+    // Global packages are installed in GOPATH, usually as tools, so they are neither used nor unused
+    let globals: Vec<&str> = manifest.packages.global.iter().map(|pkg| pkg.as_str()).collect();
+
+    let mut used = BTreeSet::new();
+    let mut missing = BTreeSet::new();
+    for import in &project_imports {
+        if imports::is_standard(import) || manifest.info.import.as_ref().map_or(false, |project| imports::provides(project, import)) || globals.iter().any(|pkg| imports::provides(pkg, import)) {
+            continue
+        }
+        match packages.iter().find(|pkg| imports::provides(pkg, import)) {
+            Some(pkg) => {
+                used.insert(*pkg);
+            },
+            None => {
+                missing.insert(helpers::repo_root(import));
+            },
+        }
+    }
+    let unused: Vec<&str> = packages.iter().filter(|pkg| !used.contains(*pkg)).cloned().collect();
+
+    if unused.is_empty() && missing.is_empty() {
+        logger.verbose("Tidy project", "all packages are used and no package is missing");
+        return
+    }
+
+    if !unused.is_empty() {
+        print_header("Unused packages", unused.len());
+        print_str_packages(&JsonValue::from(unused.clone()));
+    }
+    if !missing.is_empty() {
+        print_header("Missing packages", missing.len());
+        print_str_packages(&JsonValue::from(missing.iter().map(|pkg| pkg.as_str()).collect::<Vec<&str>>()));
+    }
+
+    if should_remove {
+        for pkg in &unused {
+            package::remove(pkg, logger);
+        }
+    }
+    if should_add {
+        let mut failed = 0;
+        for pkg in &missing {
+            if let Err(e) = package::try_get(pkg.as_str(), None, None, no_prompt, false, false, ctx, logger) {
+                logger.error(format!("unable to add `{}`: {}", pkg, e));
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            logger.fatal(format!("{} missing package(s) could not be added", failed));
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use inner::logger::Logger;
+use inner::manifest::{Manifest, Lock, LoadError};
+use std::path::Path;
+use std::io::ErrorKind;
+use std::process;
+
+pub fn validate(logger: &Logger) {
+    let mut is_valid = true;
+
+    match Manifest::load(Path::new("rubigo.json")) {
+        Ok(_) => logger.verbose("Valid file", "rubigo.json"),
+        Err(e) => {
+            report(e, "rubigo.json", logger);
+            is_valid = false;
+        },
+    }
+
+    match Lock::load(Path::new("rubigo.lock")) {
+        Ok(_) => logger.verbose("Valid file", "rubigo.lock"),
+        Err(LoadError::Io(ref e)) if e.kind() == ErrorKind::NotFound => logger.verbose("Skip file", "rubigo.lock"),
+        Err(e) => {
+            report(e, "rubigo.lock", logger);
+            is_valid = false;
+        },
+    }
+
+    if !is_valid {
+        process::exit(1);
+    }
+}
+
+fn report(err: LoadError, file_name: &str, logger: &Logger) {
+    match err {
+        LoadError::Io(e) => logger.error(format!("unable to read `{}`: {}", file_name, e)),
+        LoadError::Invalid(violations) => for violation in violations {
+            logger.error(violation);
+        },
+    }
+}
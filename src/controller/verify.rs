@@ -0,0 +1,50 @@
+use inner::logger::Logger;
+use inner::{integrity, helpers};
+use inner::vendor::VENDOR_DIR;
+use inner::manifest::Lock;
+use std::path::Path;
+use std::process;
+
+pub fn verify(logger: &Logger) {
+    let lock = match Lock::load(Path::new("rubigo.lock")) {
+        Ok(lock) => lock,
+        Err(e) => {
+            logger.fatal(format!("unable to read `rubigo.lock`: {}", e));
+            return
+        },
+    };
+
+    let mut mismatches = 0;
+    for pkg in &lock.git {
+        let expected = match pkg.hash {
+            Some(ref hash) => hash,
+            None => {
+                logger.error(format!("no hash recorded for `{}`, run `rubigo update` to record it", pkg.import));
+                mismatches += 1;
+                continue
+            },
+        };
+        let pkg_path_buf = helpers::get_path_from_url(helpers::strip_url_scheme(pkg.import.as_str()).as_str(), Path::new(VENDOR_DIR));
+        if !pkg_path_buf.is_dir() {
+            logger.error(format!("the package `{}` is missing from `vendor` directory", pkg.import));
+            mismatches += 1;
+            continue
+        }
+        match integrity::hash_dir(pkg_path_buf.as_path()) {
+            Ok(ref actual) if actual == expected => logger.verbose("Verify package", &pkg.import),
+            Ok(actual) => {
+                logger.error(format!("hash mismatch for `{}`: expected {}, found {}", pkg.import, expected, actual));
+                mismatches += 1;
+            },
+            Err(e) => {
+                logger.error(format!("unable to hash `{}`: {}", pkg.import, e));
+                mismatches += 1;
+            },
+        }
+    }
+
+    if mismatches > 0 {
+        logger.error(format!("{} of {} package(s) failed verification", mismatches, lock.git.len()));
+        process::exit(1);
+    }
+}
@@ -0,0 +1,254 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::cell::RefCell;
+use git2::{Config, Cred, Error, ErrorCode, RemoteCallbacks, SSH_KEY, USERNAME, USER_PASS_PLAINTEXT};
+use json::JsonValue;
+
+pub const TOKEN_ENV: &str = "RUBIGO_GIT_TOKEN";
+pub const METHODS: &[Method] = &[Method::SshAgent, Method::SshKey, Method::CredentialHelper, Method::Netrc, Method::Token];
+const SSH_KEY_FILES: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    SshAgent,
+    SshKey,
+    CredentialHelper,
+    Netrc,
+    Token,
+}
+
+impl Method {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Method::SshAgent => "ssh-agent",
+            Method::SshKey => "ssh-key",
+            Method::CredentialHelper => "credential-helper",
+            Method::Netrc => "netrc",
+            Method::Token => "token",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Method> {
+        METHODS.iter().cloned().find(|method| method.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostConfig {
+    pub username: Option<String>,
+    pub ssh_keys: Vec<PathBuf>,
+    pub token_env: String,
+    pub methods: Vec<Method>,
+}
+
+pub fn host_config(config: &JsonValue, host: &str) -> Result<HostConfig, String> {
+    let host_value = &config["hosts"][host];
+    let mut methods = Vec::new();
+    if host_value["methods"].is_array() {
+        for name in host_value["methods"].members() {
+            match name.as_str().and_then(Method::from_name) {
+                Some(method) => methods.push(method),
+                None => return Err(format!("unknown authentication method `{}` for `{}`, expected one of {}", name, host, METHODS.iter().map(|method| format!("`{}`", method.name())).collect::<Vec<String>>().join(", "))),
+            }
+        }
+    } else {
+        methods.extend_from_slice(METHODS);
+    }
+    let ssh_keys = match host_value["ssh_key"].as_str() {
+        Some(key) => vec![expand_home(key)],
+        None => SSH_KEY_FILES.iter().map(|name| expand_home(format!("~/.ssh/{}", name).as_str())).filter(|key| key.is_file()).collect(),
+    };
+    Ok(HostConfig {
+        username: host_value["username"].as_str().map(|name| name.to_owned()),
+        ssh_keys,
+        token_env: host_value["token_env"].as_str().unwrap_or(TOKEN_ENV).to_owned(),
+        methods,
+    })
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+            return PathBuf::from(home).join(path.trim_start_matches('~').trim_start_matches('/'))
+        }
+    }
+    PathBuf::from(path)
+}
+
+pub fn url_host(url: &str) -> Option<String> {
+    let rest = match url.find("://") {
+        Some(index) => &url[index + 3..],
+        None => url,
+    };
+    let authority = rest.split('/').next()?;
+    let authority = match authority.rfind('@') {
+        Some(at) => &authority[at + 1..],
+        None => authority,
+    };
+    let host = authority.split(':').next()?;
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+// Returns the login and password of `host`, or those of the `default` entry
+pub fn netrc_credentials(contents: &str, host: &str) -> Option<(String, String)> {
+    let mut tokens = contents.split_whitespace();
+    let mut matched: Option<(Option<String>, Option<String>)> = None;
+    let mut default: Option<(Option<String>, Option<String>)> = None;
+    let mut current: Option<bool> = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                if matched.is_some() {
+                    break
+                }
+                let is_host = tokens.next().map_or(false, |machine| machine.eq_ignore_ascii_case(host));
+                if is_host {
+                    matched = Some((None, None));
+                }
+                current = Some(is_host);
+            },
+            "default" => {
+                if matched.is_some() {
+                    break
+                }
+                default = Some((None, None));
+                current = None;
+            },
+            "login" | "password" => {
+                let value = tokens.next().map(|value| value.to_owned());
+                let entry = match current {
+                    Some(true) => matched.as_mut(),
+                    Some(false) => None,
+                    None => default.as_mut(),
+                };
+                if let Some(entry) = entry {
+                    if token == "login" {
+                        entry.0 = value;
+                    } else {
+                        entry.1 = value;
+                    }
+                }
+            },
+            "account" => {
+                tokens.next();
+            },
+            _ => {},
+        }
+    }
+    match matched.or(default) {
+        Some((Some(login), Some(password))) => Some((login, password)),
+        _ => None,
+    }
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path))
+    }
+    let home = PathBuf::from(env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?);
+    [".netrc", "_netrc"].iter().map(|name| home.join(name)).find(|path| path.is_file())
+}
+
+fn read_netrc(host: &str) -> Option<(String, String)> {
+    let mut contents = String::new();
+    File::open(netrc_path()?).and_then(|mut file| file.read_to_string(&mut contents)).ok()?;
+    netrc_credentials(contents.as_str(), host)
+}
+
+#[derive(Default)]
+struct Attempts {
+    tried: Vec<String>,
+    exhausted: bool,
+}
+
+// Credentials are tried in the order of `methods` of the host, each one at most once
+pub struct Auth {
+    host: String,
+    config: HostConfig,
+    attempts: Rc<RefCell<Attempts>>,
+}
+
+impl Auth {
+    pub fn new(url: &str, config: &JsonValue) -> Result<Auth, String> {
+        let host = url_host(url).unwrap_or_default();
+        Ok(Auth {
+            config: host_config(config, host.as_str())?,
+            host,
+            attempts: Rc::new(RefCell::new(Attempts::default())),
+        })
+    }
+
+    pub fn remote_callbacks(&self) -> RemoteCallbacks<'static> {
+        let mut candidates: Vec<(Method, Option<PathBuf>)> = Vec::new();
+        for method in &self.config.methods {
+            match *method {
+                Method::SshKey => candidates.extend(self.config.ssh_keys.iter().map(|key| (Method::SshKey, Some(key.clone())))),
+                method => candidates.push((method, None)),
+            }
+        }
+        let config = self.config.clone();
+        let host = self.host.clone();
+        let attempts = self.attempts.clone();
+        let mut next = 0;
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username_from_url, allowed| {
+            let username = username_from_url.map(|name| name.to_owned()).or_else(|| config.username.clone());
+            if allowed.contains(USERNAME) {
+                return Cred::username(username.as_deref().unwrap_or("git"))
+            }
+            while next < candidates.len() {
+                let (method, ref key) = candidates[next];
+                next += 1;
+                let ssh_username = username.as_deref().unwrap_or("git");
+                let (cred, description) = match method {
+                    Method::SshAgent if allowed.contains(SSH_KEY) => (Cred::ssh_key_from_agent(ssh_username), method.name().to_owned()),
+                    Method::SshKey if allowed.contains(SSH_KEY) => match *key {
+                        Some(ref key) => (Cred::ssh_key(ssh_username, None, key.as_path(), None), format!("{} `{}`", method.name(), key.to_string_lossy())),
+                        None => continue,
+                    },
+                    Method::CredentialHelper if allowed.contains(USER_PASS_PLAINTEXT) => (Config::open_default().and_then(|git_config| Cred::credential_helper(&git_config, url, username.as_deref())), method.name().to_owned()),
+                    Method::Netrc if allowed.contains(USER_PASS_PLAINTEXT) => match read_netrc(host.as_str()) {
+                        Some((login, password)) => (Cred::userpass_plaintext(login.as_str(), password.as_str()), method.name().to_owned()),
+                        None => continue,
+                    },
+                    Method::Token if allowed.contains(USER_PASS_PLAINTEXT) => match env::var(config.token_env.as_str()) {
+                        Ok(ref token) if !token.is_empty() => (Cred::userpass_plaintext(username.as_deref().unwrap_or("oauth2"), token.as_str()), format!("{} `${}`", method.name(), config.token_env)),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                if let Ok(cred) = cred {
+                    attempts.borrow_mut().tried.push(description);
+                    return Ok(cred)
+                }
+            }
+            attempts.borrow_mut().exhausted = true;
+            Err(Error::from_str("no more credentials to try"))
+        });
+        callbacks
+    }
+
+    pub fn is_auth_error(&self, e: &Error) -> bool {
+        e.code() == ErrorCode::Auth || self.attempts.borrow().exhausted
+    }
+
+    pub fn explain(&self, e: Error) -> String {
+        if !self.is_auth_error(&e) {
+            return e.to_string()
+        }
+        let attempts = self.attempts.borrow();
+        let tried = if attempts.tried.is_empty() {
+            "no available method".to_owned()
+        } else {
+            attempts.tried.join(", ")
+        };
+        format!("authentication to `{}` failed (tried {}): {}", self.host, tried, e)
+    }
+}
@@ -0,0 +1,174 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CACHE_DIR_ENV: &str = "RUBIGO_CACHE_DIR";
+pub const NO_CACHE_ENV: &str = "RUBIGO_NO_CACHE";
+pub const MAX_SIZE_ENV: &str = "RUBIGO_CACHE_MAX_SIZE";
+pub const DEFAULT_MAX_SIZE: u64 = 2048 * 1024 * 1024;
+const MARKER_FILE: &str = ".rubigo-cache";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: u64,
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    if env::var_os(NO_CACHE_ENV).map_or(false, |value| !value.is_empty()) {
+        return None
+    }
+    if let Some(dir) = env::var_os(CACHE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir))
+    }
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("rubigo"))
+    }
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).filter(|dir| !dir.is_empty()).map(|home| PathBuf::from(home).join(".cache").join("rubigo"))
+}
+
+// Every component of the url is kept, `.` and `..` are escaped instead of being resolved so that different urls never share a mirror
+pub fn mirror_name(vcs_name: &str, repo_url: &str) -> String {
+    let path = match repo_url.find("://") {
+        Some(index) => &repo_url[index + 3..],
+        None => repo_url,
+    };
+    let path = match (path.find('@'), path.find(':')) {
+        (Some(at), Some(colon)) if at < colon && !path[..at].contains('/') => format!("{}/{}", &path[at + 1..colon], &path[colon + 1..]),
+        _ => path.to_owned(),
+    };
+    let path = path.trim_end_matches(&['/', '\\'][..]);
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let mut parts = vec![vcs_name.to_owned()];
+    for part in path.split(&['/', '\\'][..]).filter(|part| !part.is_empty()) {
+        parts.push(match part {
+            "." => "%2E".to_owned(),
+            ".." => "%2E%2E".to_owned(),
+            _ => escape(part),
+        });
+    }
+    parts.join("/")
+}
+
+fn escape(part: &str) -> String {
+    let mut escaped = String::new();
+    for c in part.chars() {
+        match c {
+            '%' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => escaped.push_str(format!("%{:02X}", c as u32).as_str()),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn mirror_path(cache_dir: Option<&Path>, vcs_name: &str, repo_url: &str) -> Option<PathBuf> {
+    Some(cache_dir?.join(mirror_name(vcs_name, repo_url)))
+}
+
+pub fn touch(mirror_path: &Path) -> io::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    File::create(mirror_path.join(MARKER_FILE))?.write_all(format!("{}\n", now).as_bytes())
+}
+
+pub fn mirrors() -> io::Result<Vec<Mirror>> {
+    let mut result = Vec::new();
+    if let Some(dir) = cache_dir() {
+        if dir.is_dir() {
+            collect_mirrors(dir.as_path(), String::new(), &mut result)?;
+        }
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+fn collect_mirrors(dir_path: &Path, prefix: String, mirrors: &mut Vec<Mirror>) -> io::Result<()> {
+    let marker_path = dir_path.join(MARKER_FILE);
+    if marker_path.is_file() {
+        let mut contents = String::new();
+        File::open(marker_path.as_path())?.read_to_string(&mut contents)?;
+        mirrors.push(Mirror {
+            name: prefix,
+            path: dir_path.to_path_buf(),
+            size: dir_size(dir_path)?,
+            last_used: contents.trim().parse().unwrap_or(0),
+        });
+        return Ok(())
+    }
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel_path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        collect_mirrors(entry.path().as_path(), rel_path, mirrors)?;
+    }
+    Ok(())
+}
+
+pub fn dir_size(dir_path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(entry.path().as_path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+// The least recently used mirrors are evicted until the cache fits in `max_size` bytes
+pub fn evictions(mirrors: &[Mirror], max_size: u64) -> Vec<usize> {
+    let mut total: u64 = mirrors.iter().map(|mirror| mirror.size).sum();
+    let mut indices: Vec<usize> = (0..mirrors.len()).collect();
+    indices.sort_by(|a, b| mirrors[*a].last_used.cmp(&mirrors[*b].last_used).then_with(|| mirrors[*a].name.cmp(&mirrors[*b].name)));
+    let mut evicted = Vec::new();
+    for index in indices {
+        if total <= max_size {
+            break
+        }
+        total -= mirrors[index].size;
+        evicted.push(index);
+    }
+    evicted
+}
+
+pub fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let index = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+    let number: u64 = size[..index].parse().ok()?;
+    let multiplier: u64 = match size[index..].trim().to_lowercase().as_str() {
+        "" | "m" | "mb" | "mib" => 1024 * 1024,
+        "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
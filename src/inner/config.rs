@@ -0,0 +1,94 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use json::{self, JsonValue};
+
+pub const CONFIG_ENV: &str = "RUBIGO_CONFIG";
+pub const CONFIG_FILE: &str = "config.json";
+pub const PROJECT_CONFIG_DIR: &str = ".rubigo";
+
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path))
+    }
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("rubigo").join(CONFIG_FILE))
+    }
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).filter(|dir| !dir.is_empty()).map(|home| PathBuf::from(home).join(".config").join("rubigo").join(CONFIG_FILE))
+}
+
+// The project configuration in `.rubigo/config.json` of `project_dir` overrides the entries of the user configuration
+pub fn load(project_dir: &Path) -> Result<JsonValue, String> {
+    let mut config = match config_path() {
+        Some(path) => read_config(path.as_path())?,
+        None => JsonValue::new_object(),
+    };
+    merge(&mut config, &load_project(project_dir)?);
+    Ok(config)
+}
+
+pub fn load_project(project_dir: &Path) -> Result<JsonValue, String> {
+    read_config(project_dir.join(PROJECT_CONFIG_DIR).join(CONFIG_FILE).as_path())
+}
+
+fn read_config(path: &Path) -> Result<JsonValue, String> {
+    if !path.is_file() {
+        return Ok(JsonValue::new_object())
+    }
+    let mut contents = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        return Err(format!("unable to read `{}`: {}", path.to_string_lossy(), e))
+    }
+    match json::parse(contents.as_str()) {
+        Ok(ref config) if !config.is_object() => Err(format!("`{}` should contain a JSON object", path.to_string_lossy())),
+        Ok(config) => Ok(config),
+        Err(e) => Err(format!("unable to parse `{}`: {}", path.to_string_lossy(), e)),
+    }
+}
+
+pub fn merge(config: &mut JsonValue, other: &JsonValue) {
+    for (key, value) in other.entries() {
+        if config[key].is_object() && value.is_object() {
+            for (name, entry) in value.entries() {
+                config[key][name] = entry.clone();
+            }
+        } else {
+            config[key] = value.clone();
+        }
+    }
+}
+
+// Works like `url.<base>.insteadOf` of git, the longest matching prefix wins and a prefix without a scheme also matches urls with any scheme
+pub fn rewrite_url(config: &JsonValue, url: &str) -> Option<String> {
+    let schemeless = match url.find("://") {
+        Some(index) => &url[index + 3..],
+        None => url,
+    };
+    let mut rewritten: Option<(usize, String)> = None;
+    for (base, rule) in config["url"].entries() {
+        let prefixes: Vec<&str> = if rule["instead_of"].is_array() {
+            rule["instead_of"].members().filter_map(|prefix| prefix.as_str()).collect()
+        } else {
+            rule["instead_of"].as_str().into_iter().collect()
+        };
+        for prefix in prefixes {
+            let prefix = prefix.trim_end_matches('*');
+            let target = if prefix.contains("://") {
+                url
+            } else {
+                schemeless
+            };
+            if prefix.is_empty() || !target.starts_with(prefix) || rewritten.as_ref().map_or(false, |selected| selected.0 >= prefix.len()) {
+                continue
+            }
+            rewritten = Some((prefix.len(), format!("{}{}", base, &target[prefix.len()..])));
+        }
+    }
+    rewritten.map(|(_, url)| url)
+}
+
+// Returns the url which should be used to clone or fetch `url`
+pub fn clone_url(config: &JsonValue, url: &str) -> String {
+    rewrite_url(config, url).unwrap_or_else(|| url.to_owned())
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use json::JsonValue;
+use inner::{cache, config, helpers, jobs};
+use inner::vendor::VENDOR_DIR;
+
+// The settings of a command, which are passed to the operations instead of being read again by each of them
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub config: JsonValue,
+    // `None` if the cache is disabled
+    pub cache_dir: Option<PathBuf>,
+    // Only the cache and `vendor` directory are used without accessing the network
+    pub offline: bool,
+    // The number of packages which are installed at once, instead of the `jobs` entry of the configuration
+    pub jobs: Option<usize>,
+    // The directory which packages are installed in, which is a staged copy of `vendor` directory during an update
+    pub vendor_dir: PathBuf,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            config: JsonValue::new_object(),
+            cache_dir: None,
+            offline: false,
+            jobs: None,
+            vendor_dir: PathBuf::from(VENDOR_DIR),
+        }
+    }
+}
+
+impl Context {
+    pub fn load(project_dir: &Path) -> Result<Context, String> {
+        Ok(Context {
+            config: config::load(project_dir)?,
+            cache_dir: cache::cache_dir(),
+            offline: helpers::is_offline(),
+            jobs: jobs::env_count()?,
+            vendor_dir: PathBuf::from(VENDOR_DIR),
+        })
+    }
+}
@@ -1,7 +1,22 @@
-use git2::{Repository, Object, BranchType};
-use semver::{Version, VersionReq};
-use regex::Regex;
-use inner::logger::Logger;
+use std::fs::{remove_dir, remove_dir_all, rename};
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use git2::{Repository, ObjectType, BranchType, ResetType, Oid, Error, ErrorClass, FetchOptions, Progress, RemoteCallbacks};
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use json::JsonValue;
+use inner::auth::Auth;
+use inner::{cache, jobs};
+use inner::logger;
+use inner::network::{self, Failure};
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+static CLONES: AtomicUsize = AtomicUsize::new(0);
 
 pub fn get_latest_commit(repo: &Repository) -> Option<String> {
     match repo.head() {
@@ -40,100 +55,209 @@ pub fn get_current_branch(repo: &Repository) -> Option<String> {
     }
 }
 
-pub fn get_latest_version(repo: &Repository, version_rule: Option<&VersionReq>) -> Option<(String, Version)> {
-    let mut version = None;
-    match repo.tag_names(None) {
-        Ok(tag_names) => {
-            let mut selected_tag = None;
-            let re = match Regex::new(r"^v?([0-9]+)[.]?([0-9]*)[.]?([0-9]*)([-]?.*)") {
-                Ok(re) => re,
-                _ => return version,
-            };
-            for t in tag_names.iter() {
-                let tag_name = match t {
-                    Some(name) => name,
-                    None => continue,
-                };
-                let tag_version_str = match re.captures(t.unwrap()) {
-                    Some(caps) => format!("{}.{}.{}{}",
-                                          match caps.get(1) {
-                                              Some(c) => {
-                                                  let n = c.as_str();
-                                                  if n.is_empty() {
-                                                      continue
-                                                  } else {
-                                                      n
-                                                  }
-                                              },
-                                              _ => continue,
-                                          },
-                                          match caps.get(2) {
-                                              Some(c) => {
-                                                  let n = c.as_str();
-                                                  if n.is_empty() {
-                                                      "0"
-                                                  } else {
-                                                      n
-                                                  }
-                                              },
-                                              _ => "0",
-                                          },
-                                          match caps.get(3) {
-                                              Some(c) => {
-                                                  let n = c.as_str();
-                                                  if n.is_empty() {
-                                                      "0"
-                                                  } else {
-                                                      n
-                                                  }
-                                              },
-                                              _ => "0",
-                                          },
-                                          match caps.get(4) {
-                                              Some(c) => c.as_str(),
-                                              _ => "",
-                                          }),
-                    None => continue,
-                };
-                let tag_version = match Version::parse(tag_version_str.as_str()) {
-                    Ok(ver) => ver,
-                    _ => continue,
-                };
-                if (version_rule.is_none() || version_rule.unwrap().matches(&tag_version)) && (selected_tag.is_none() || tag_version > selected_tag.clone().unwrap()) {
-                    version = Some((tag_name.to_owned(), tag_version.clone()));
-                    selected_tag = Some(tag_version);
-                }
-            }
-        },
-        _ => (),
+pub fn clone(url: &str, dir_path: &Path, bare: bool, config: &JsonValue) -> Result<Repository, String> {
+    // Each attempt clones into its own directory next to `dir_path`, so an abandoned attempt never writes into it
+    let attempts_path = dir_path.with_file_name(format!(".rubigo-clone-{}-{}", process::id(), CLONES.fetch_add(1, Ordering::SeqCst)));
+    let c_url = url.to_owned();
+    let c_attempts_path = attempts_path.clone();
+    let attempts = AtomicUsize::new(0);
+    let result = transfer(url, config, move |fetch_options| {
+        let attempt_path = c_attempts_path.join(attempts.fetch_add(1, Ordering::SeqCst).to_string());
+        RepoBuilder::new().bare(bare).fetch_options(fetch_options).with_checkout(checkout_options()).clone(c_url.as_str(), attempt_path.as_path()).map(|_| attempt_path)
+    }).and_then(|attempt_path| {
+        // An empty directory which was created for the clone is replaced
+        let _ = remove_dir(dir_path);
+        rename(attempt_path, dir_path).map_err(|e| e.to_string())?;
+        Repository::open(dir_path).map_err(|e| e.to_string())
+    });
+    let _ = remove_dir_all(attempts_path);
+    result
+}
+
+// Clones a local repository such as a mirror, without the network settings
+pub fn clone_local(path: &str, dir_path: &Path) -> Result<Repository, Error> {
+    RepoBuilder::new().fetch_options(local_fetch_options()).with_checkout(checkout_options()).clone(path, dir_path)
+}
+
+fn local_fetch_options() -> FetchOptions<'static> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.transfer_progress(|progress| {
+        report_transfer(&progress);
+        true
+    });
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options
+}
+
+pub fn checkout_options() -> CheckoutBuilder<'static> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.progress(|_, completed, total| if total > 0 {
+        logger::progress(checkout_status(completed, total));
+    });
+    checkout
+}
+
+pub fn transfer_status(received_objects: usize, total_objects: usize, indexed_deltas: usize, total_deltas: usize, received_bytes: usize) -> String {
+    let size = cache::format_size(received_bytes as u64);
+    if received_objects < total_objects {
+        format!("receiving objects {}/{} ({}%), {}", received_objects, total_objects, percent(received_objects, total_objects), size)
+    } else if indexed_deltas < total_deltas {
+        format!("resolving deltas {}/{} ({}%), {}", indexed_deltas, total_deltas, percent(indexed_deltas, total_deltas), size)
+    } else {
+        format!("received {} objects, {}", total_objects, size)
     }
-    version
 }
 
-pub fn get_latest_compat_version(repo: &Repository, rule_tag_name: String) -> String {
-    match VersionReq::parse(rule_tag_name.as_str()) {
-        Ok(version_rule) => match get_latest_version(repo, Some(&version_rule)) {
-            Some((tag_name, _)) => tag_name,
-            None => rule_tag_name,
-        },
-        _ => rule_tag_name,
+pub fn checkout_status(completed: usize, total: usize) -> String {
+    format!("checking out {}/{} files ({}%)", completed, total, percent(completed, total))
+}
+
+fn percent(count: usize, total: usize) -> usize {
+    count.saturating_mul(100).checked_div(total).unwrap_or(100)
+}
+
+fn report_transfer(progress: &Progress) {
+    if progress.total_objects() > 0 {
+        logger::progress(transfer_status(progress.received_objects(), progress.total_objects(), progress.indexed_deltas(), progress.total_deltas(), progress.received_bytes()));
     }
 }
 
-pub fn get_revision_object(repo: &Repository, pkg_import: String, version: String, should_retry: bool, logger: Logger) -> Option<(Object, String)> {
-    match repo.revparse_single(version.as_str()) {
-        Ok(obj) => return Some((obj, version)),
-        Err(e) => {
-            if !should_retry {
-                return None
-            }
-            match get_latest_commit(repo) {
-                Some(ver) => {
-                    logger.error(format!("the version of `{}` changed to `{}` due to {}", pkg_import, ver, e));
-                    return get_revision_object(repo, pkg_import, ver, false, logger)
+pub fn fetch_remote(repo: &Repository, config: &JsonValue) -> Result<(), String> {
+    let remotes = repo.remotes().map_err(|e| e.to_string())?;
+    let remote_name = match remotes.get(0) {
+        Some(name) => name.to_owned(),
+        None => return Err("unable to get remote name".to_owned()),
+    };
+    let url = repo.find_remote(remote_name.as_str()).map_err(|e| e.to_string())?.url().unwrap_or_default().to_owned();
+    let repo_path = repo.path().to_path_buf();
+    transfer(url.as_str(), config, move |mut fetch_options| Repository::open(repo_path.as_path())?.find_remote(remote_name.as_str())?.fetch(&[], Some(&mut fetch_options), None))
+}
+
+pub fn origin_url(repo: &Repository) -> Option<String> {
+    repo.find_remote("origin").ok()?.url().map(|url| url.to_owned())
+}
+
+pub fn fetch_mirror(mirror: &Repository, config: &JsonValue) -> Result<(), String> {
+    let url = mirror.find_remote("origin").map_err(|e| e.to_string())?.url().unwrap_or_default().to_owned();
+    let mirror_path = mirror.path().to_path_buf();
+    transfer(url.as_str(), config, move |mut fetch_options| Repository::open(mirror_path.as_path())?.find_remote("origin")?.fetch(&["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"], Some(&mut fetch_options), None))
+}
+
+// The last time a transfer showed activity and whether it was abandoned
+#[derive(Default)]
+struct Activity {
+    last: Mutex<Option<Instant>>,
+    is_cancelled: AtomicBool,
+}
+
+impl Activity {
+    // Returns false to stop the transfer once it is abandoned
+    fn touch(&self) -> bool {
+        if let Ok(mut last) = self.last.lock() {
+            *last = Some(Instant::now());
+        }
+        !self.is_cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// Runs a transfer from `url` with the network settings, it is retried with a new authentication on transient errors
+// libgit2 has neither a connect nor a read timeout, so each attempt runs in its own thread which is abandoned once it shows no activity
+// for `connect_timeout` and `timeout` seconds before the first response, or for `timeout` seconds after it
+fn transfer<T, F>(url: &str, config: &JsonValue, operation: F) -> Result<T, String> where T: Send + 'static, F: Fn(FetchOptions<'static>) -> Result<T, Error> + Send + Sync + 'static {
+    let settings = network::settings(config)?;
+    let operation = Arc::new(operation);
+    network::retry(&settings, || {
+        let _permit = jobs::acquire(url, config).map_err(Failure::permanent)?;
+        let activity = Arc::new(Activity::default());
+        let (tx, rx) = channel();
+        let (c_url, c_config, c_operation, c_activity) = (url.to_owned(), config.clone(), operation.clone(), activity.clone());
+        let task = logger::current_task();
+        thread::spawn(move || {
+            logger::join_task(task);
+            let _ = tx.send(attempt(c_url.as_str(), &c_config, c_operation.as_ref(), c_activity));
+        });
+        let started = Instant::now();
+        loop {
+            match rx.recv_timeout(WATCH_INTERVAL) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Disconnected) => return Err(Failure::permanent(format!("the transfer from `{}` stopped unexpectedly", url))),
+                Err(RecvTimeoutError::Timeout) => {
+                    let (since, limit) = match activity.last.lock().ok().and_then(|last| *last) {
+                        Some(last) => (last, settings.timeout),
+                        None => (started, settings.connect_timeout + settings.timeout),
+                    };
+                    if settings.timeout.as_secs() > 0 && since.elapsed() >= limit {
+                        activity.is_cancelled.store(true, Ordering::SeqCst);
+                        return Err(Failure::transient(format!("the transfer from `{}` made no progress for {} seconds", url, limit.as_secs())))
+                    }
                 },
-                None => return None,
             }
-        },
+        }
+    })
+}
+
+fn attempt<T, F>(url: &str, config: &JsonValue, operation: &F, activity: Arc<Activity>) -> Result<T, Failure> where F: Fn(FetchOptions<'static>) -> Result<T, Error> {
+    let auth = Auth::new(url, config).map_err(Failure::permanent)?;
+    let mut callbacks = auth.remote_callbacks();
+    let transfer_activity = activity.clone();
+    callbacks.transfer_progress(move |progress| {
+        report_transfer(&progress);
+        transfer_activity.touch()
+    });
+    callbacks.sideband_progress(move |_| activity.touch());
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    operation(fetch_options).map_err(|e| {
+        let is_transient = !auth.is_auth_error(&e) && (e.class() == ErrorClass::Net || e.class() == ErrorClass::Os);
+        Failure {
+            message: auth.explain(e),
+            is_transient,
+        }
+    })
+}
+
+pub fn fetch_from(repo: &Repository, url: &str) -> Result<(), Error> {
+    repo.remote_anonymous(url)?.fetch(&["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"], Some(&mut local_fetch_options()), None)
+}
+
+pub fn update_branches(repo: &Repository) {
+    if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
+        for (branch, _) in branches.flatten() {
+            let branch_ref_name = match branch.get().name() {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let remote_name = match branch.upstream().ok().and_then(|remote_branch| remote_branch.name().ok().and_then(|name| name.map(|name| name.to_owned()))) {
+                Some(name) => name,
+                None => continue,
+            };
+            let remote_object = match repo.revparse_single(remote_name.as_str()) {
+                Ok(obj) => obj,
+                _ => continue,
+            };
+            if repo.set_head(branch_ref_name.as_str()).is_ok() {
+                let _ = repo.reset(&remote_object, ResetType::Hard, None);
+            }
+        }
+    }
+}
+
+pub fn commits_behind(repo: &Repository, commit: &str, branch: Option<&str>) -> Option<(String, usize)> {
+    let reference = match branch {
+        Some(name) => repo.find_reference(format!("refs/remotes/origin/{}", name).as_str()).ok()?,
+        None => repo.find_reference("refs/remotes/origin/HEAD").ok()?.resolve().ok()?,
+    };
+    let branch_name = reference.shorthand()?.trim_start_matches("origin/").to_owned();
+    let (_, behind) = repo.graph_ahead_behind(Oid::from_str(commit).ok()?, reference.target()?).ok()?;
+    Some((branch_name, behind))
+}
+
+pub fn resolve_commit(repo: &Repository, revision: &str) -> Option<String> {
+    for rev in &[format!("origin/{}", revision), revision.to_owned()] {
+        if let Ok(commit) = repo.revparse_single(rev.as_str()).and_then(|obj| obj.peel(ObjectType::Commit)) {
+            return Some(format!("{}", commit.id()))
+        }
     }
+    None
 }
@@ -1,13 +1,26 @@
 use std::process::Command;
 use std::ffi::OsStr;
+use std::env;
+use std::path::PathBuf;
 
-pub fn get(package_name: &str, should_update: bool) -> bool {
+pub fn get(package_name: &str, should_update: bool, offline: bool) -> bool {
+    if offline {
+        return is_installed(package_name)
+    }
     match should_update {
         true => run(&["get", "-u", package_name]),
         false => run(&["get", package_name])
     }
 }
 
+pub fn is_installed(package_name: &str) -> bool {
+    let gopaths: Vec<PathBuf> = match env::var_os("GOPATH") {
+        Some(ref gopath) if !gopath.is_empty() => env::split_paths(gopath).collect(),
+        _ => env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(|home| PathBuf::from(home).join("go")).into_iter().collect(),
+    };
+    gopaths.iter().any(|gopath| gopath.join("src").join(package_name).is_dir())
+}
+
 fn run<S: AsRef<OsStr>>(args: &[S]) -> bool {
     match Command::new("go").args(args).status() {
         Ok(exit_status) => exit_status.success(),
@@ -0,0 +1,302 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use json::{self, JsonValue};
+use regex::Regex;
+use inner::{auth, http, modproxy};
+use inner::context::Context;
+
+// Hosts whose repositories are found without the `?go-get=1` protocol
+const KNOWN_HOSTS: &[&str] = &["github.com", "bitbucket.org"];
+const CACHE_FILE: &str = "go-import.json";
+// The schemes accepted in the repository of a `go-import` meta tag, like `go get`
+const REPO_SCHEMES: &[&str] = &["https", "http", "git", "ssh", "git+ssh"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaImport {
+    pub prefix: String,
+    pub vcs: String,
+    pub repo: String,
+    pub source: Option<MetaSource>,
+}
+
+// The templates of a `go-source` meta tag which link to the sources of the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaSource {
+    pub prefix: String,
+    pub home: String,
+    pub directory: String,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GopkgIn {
+    pub prefix: String,
+    pub repo: String,
+    pub major: u64,
+}
+
+impl MetaImport {
+    fn to_json(&self) -> JsonValue {
+        let mut value = object!{
+            "vcs" => self.vcs.as_str(),
+            "repo" => self.repo.as_str()
+        };
+        if let Some(ref source) = self.source {
+            value["source"] = object!{
+                "prefix" => source.prefix.as_str(),
+                "home" => source.home.as_str(),
+                "directory" => source.directory.as_str(),
+                "file" => source.file.as_str()
+            };
+        }
+        value
+    }
+
+    fn from_json(prefix: &str, value: &JsonValue) -> Option<MetaImport> {
+        let source = &value["source"];
+        Some(MetaImport {
+            prefix: prefix.to_owned(),
+            vcs: value["vcs"].as_str()?.to_owned(),
+            repo: value["repo"].as_str()?.to_owned(),
+            source: match (source["prefix"].as_str(), source["home"].as_str(), source["directory"].as_str(), source["file"].as_str()) {
+                (Some(prefix), Some(home), Some(directory), Some(file)) => Some(MetaSource {
+                    prefix: prefix.to_owned(),
+                    home: home.to_owned(),
+                    directory: directory.to_owned(),
+                    file: file.to_owned(),
+                }),
+                _ => None,
+            },
+        })
+    }
+
+    // Like `go get`, a repository which could be taken as an option or has an unexpected scheme is rejected
+    fn check_repo(&self) -> Result<(), String> {
+        let scheme = self.repo.find("://").map(|index| &self.repo[..index]);
+        if self.repo.starts_with('-') || !scheme.map_or(false, |scheme| REPO_SCHEMES.contains(&scheme)) {
+            return Err(format!("the repository `{}` of `{}` is not a valid url", self.repo, self.prefix))
+        }
+        Ok(())
+    }
+}
+
+// The resolved prefixes and the import paths which are known to have no `go-import` meta tag
+#[derive(Default)]
+struct Resolved {
+    imports: HashMap<String, MetaImport>,
+    missing: HashSet<String>,
+}
+
+lazy_static! {
+    static ref RESOLVED: Mutex<Resolved> = Mutex::new(Resolved::default());
+}
+
+fn resolved() -> &'static Mutex<Resolved> {
+    &RESOLVED
+}
+
+fn has_prefix(import_path: &str, prefix: &str) -> bool {
+    import_path == prefix || (import_path.starts_with(prefix) && import_path[prefix.len()..].starts_with('/'))
+}
+
+// Only the `meta` tags of the `head` are considered, like `go get` does
+// A `go-source` tag is attached to the `go-import` tag of the same repository root
+pub fn parse_meta(html: &str) -> Vec<MetaImport> {
+    let head = match html.to_ascii_lowercase().find("<body") {
+        Some(index) => &html[..index],
+        None => html,
+    };
+    let mut imports = Vec::new();
+    let mut sources = Vec::new();
+    let (meta_re, attr_re) = match (Regex::new(r"(?is)<meta\s([^>]*)>"), Regex::new(r#"(?s)([A-Za-z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)) {
+        (Ok(meta_re), Ok(attr_re)) => (meta_re, attr_re),
+        _ => return imports,
+    };
+    for meta in meta_re.captures_iter(head) {
+        let mut name = None;
+        let mut content = None;
+        for attr in attr_re.captures_iter(meta.get(1).map_or("", |m| m.as_str())) {
+            let value = attr.get(2).or_else(|| attr.get(3)).or_else(|| attr.get(4)).map_or("", |m| m.as_str());
+            match attr.get(1).map_or(String::new(), |m| m.as_str().to_lowercase()).as_str() {
+                "name" => name = Some(value.to_owned()),
+                "content" => content = Some(value.to_owned()),
+                _ => (),
+            }
+        }
+        let fields: Vec<String> = content.unwrap_or_default().split_whitespace().map(|field| field.to_owned()).collect();
+        match (name.as_deref(), fields.len()) {
+            (Some("go-import"), 3) => imports.push(MetaImport {
+                prefix: fields[0].clone(),
+                vcs: fields[1].clone(),
+                repo: fields[2].clone(),
+                source: None,
+            }),
+            (Some("go-source"), 4) => sources.push(MetaSource {
+                prefix: fields[0].clone(),
+                home: fields[1].clone(),
+                directory: fields[2].clone(),
+                file: fields[3].clone(),
+            }),
+            _ => (),
+        }
+    }
+    for import in imports.iter_mut() {
+        import.source = sources.iter().find(|source| has_prefix(import.prefix.as_str(), source.prefix.as_str())).cloned();
+    }
+    imports
+}
+
+// Like `go get`, a `mod` tag wins over the version control system of the same path
+pub fn match_import(imports: &[MetaImport], import_path: &str) -> Result<Option<MetaImport>, String> {
+    let matched: Vec<&MetaImport> = imports.iter().filter(|meta| has_prefix(import_path, meta.prefix.as_str())).collect();
+    let modules: Vec<&&MetaImport> = matched.iter().filter(|meta| meta.vcs == modproxy::VCS_NAME).collect();
+    let import = match matched.len() {
+        0 => return Ok(None),
+        1 => matched[0],
+        _ if modules.len() == 1 => *modules[0],
+        _ => return Err(format!("multiple `go-import` meta tags match `{}`: {}", import_path, matched.iter().map(|meta| format!("`{}`", meta.prefix)).collect::<Vec<String>>().join(", "))),
+    };
+    import.check_repo()?;
+    Ok(Some(import.clone()))
+}
+
+// `gopkg.in/pkg.vN` is served from `github.com/go-pkg/pkg` and `gopkg.in/user/pkg.vN` from `github.com/user/pkg`
+pub fn gopkg_in(import_path: &str) -> Option<GopkgIn> {
+    let segments: Vec<&str> = import_path.split('/').collect();
+    if segments[0] != "gopkg.in" {
+        return None
+    }
+    let (user, index) = match segments.get(1) {
+        Some(segment) if segment.contains(".v") => (None, 1),
+        Some(user) if segments.len() > 2 => (Some(*user), 2),
+        _ => return None,
+    };
+    let segment = segments[index];
+    let (name, major) = segment.rfind(".v").map(|dot| (&segment[..dot], &segment[dot + 2..]))?;
+    if name.is_empty() || major.is_empty() || !major.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+    Some(GopkgIn {
+        prefix: segments[..index + 1].join("/"),
+        repo: match user {
+            Some(user) => format!("https://github.com/{}/{}", user, name),
+            None => format!("https://github.com/go-{}/{}", name, name),
+        },
+        major: major.parse().ok()?,
+    })
+}
+
+pub fn needs_lookup(import_path: &str) -> bool {
+    let segments: Vec<&str> = import_path.split('/').collect();
+    segments.len() > 1 && segments[0].contains('.') && !KNOWN_HOSTS.contains(&segments[0]) && !segments.iter().any(|s| s.ends_with(".git") || s.ends_with(".hg"))
+}
+
+// Returns `None` if the import path is not a vanity path or does not serve a `go-import` meta tag
+// The page is only requested over plain http if the host is `insecure` in the configuration,
+// and a page which could not be fetched falls back to the cache or fails
+pub fn resolve(import_path: &str, ctx: &Context) -> Result<Option<MetaImport>, String> {
+    if let Some(gopkg) = gopkg_in(import_path) {
+        return Ok(Some(MetaImport {
+            prefix: gopkg.prefix,
+            vcs: "git".to_owned(),
+            repo: gopkg.repo,
+            source: None,
+        }))
+    }
+    if !needs_lookup(import_path) {
+        return Ok(None)
+    }
+    if let Ok(resolved) = resolved().lock() {
+        if let Some(import) = resolved.imports.values().find(|import| has_prefix(import_path, import.prefix.as_str())) {
+            return Ok(Some(import.clone()))
+        }
+        if resolved.missing.contains(import_path) {
+            return Ok(None)
+        }
+    }
+    if ctx.offline {
+        return Ok(cached(import_path, ctx).or_else(|| golang_org(import_path)))
+    }
+    let html = match fetch(format!("https://{}?go-get=1", import_path).as_str(), &ctx.config) {
+        Ok(html) => Ok(html),
+        Err(_) if is_insecure(import_path, &ctx.config) => fetch(format!("http://{}?go-get=1", import_path).as_str(), &ctx.config),
+        Err(e) => Err(e),
+    };
+    let import = match html {
+        Ok(html) => match_import(&parse_meta(html.as_str()), import_path)?,
+        Err(e) => match cached(import_path, ctx) {
+            Some(import) => Some(import),
+            None => return Err(e),
+        },
+    };
+    if let Ok(mut resolved) = resolved().lock() {
+        match import {
+            Some(ref import) => {
+                resolved.imports.insert(import.prefix.clone(), import.clone());
+                store(import, ctx);
+            },
+            None => {
+                resolved.missing.insert(import_path.to_owned());
+            },
+        }
+    }
+    Ok(import)
+}
+
+// The repository of a path which is not resolved is guessed from the path itself
+pub fn default_repo(import_path: &str, config: &JsonValue) -> String {
+    if is_insecure(import_path, config) {
+        format!("http://{}", import_path)
+    } else {
+        format!("https://{}", import_path)
+    }
+}
+
+fn is_insecure(import_path: &str, config: &JsonValue) -> bool {
+    auth::url_host(format!("http://{}", import_path).as_str()).map_or(false, |host| config["hosts"][host.as_str()]["insecure"].as_bool() == Some(true))
+}
+
+fn fetch(url: &str, config: &JsonValue) -> Result<String, String> {
+    http::get(url, config).map(|body| String::from_utf8_lossy(&body).into_owned())
+}
+
+fn read_cache(ctx: &Context) -> JsonValue {
+    let mut contents = String::new();
+    match ctx.cache_dir.as_ref().map(|dir| File::open(dir.join(CACHE_FILE)).and_then(|mut file| file.read_to_string(&mut contents))) {
+        Some(Ok(_)) => json::parse(contents.as_str()).unwrap_or_else(|_| JsonValue::new_object()),
+        _ => JsonValue::new_object(),
+    }
+}
+
+fn cached(import_path: &str, ctx: &Context) -> Option<MetaImport> {
+    let entries = read_cache(ctx);
+    let (prefix, value) = entries.entries().find(|&(prefix, _)| has_prefix(import_path, prefix))?;
+    MetaImport::from_json(prefix, value).filter(|import| import.check_repo().is_ok())
+}
+
+// Writes are serialized by the lock of the resolved paths
+fn store(import: &MetaImport, ctx: &Context) {
+    let dir = match ctx.cache_dir {
+        Some(ref dir) => dir,
+        None => return,
+    };
+    let mut entries = read_cache(ctx);
+    entries[import.prefix.as_str()] = import.to_json();
+    let tmp_path = dir.join(format!("{}.tmp", CACHE_FILE));
+    let _ = fs::create_dir_all(dir.as_path())
+        .and_then(|_| File::create(tmp_path.as_path()))
+        .and_then(|mut file| file.write_all(entries.pretty(2).as_bytes()))
+        .and_then(|_| fs::rename(tmp_path.as_path(), dir.join(CACHE_FILE)));
+}
+
+fn golang_org(import_path: &str) -> Option<MetaImport> {
+    let name = import_path.strip_prefix("golang.org/x/")?.split('/').next()?;
+    Some(MetaImport {
+        prefix: format!("golang.org/x/{}", name),
+        vcs: "git".to_owned(),
+        repo: format!("https://go.googlesource.com/{}", name),
+        source: None,
+    })
+}
@@ -0,0 +1,225 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use sha2::{Sha256, Digest};
+use regex::Regex;
+use base64;
+use inner::modproxy;
+
+// Go 1.17 and later require go version annotations in `vendor/modules.txt`
+pub const GO_DIRECTIVE: &str = "1.16";
+pub const LOCAL_VERSION: &str = "v0.0.0";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Replace {
+    Module(String, String),
+    Dir(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Require {
+    pub path: String,
+    pub version: String,
+    pub replace: Option<Replace>,
+}
+
+pub fn module_version(version: &str, commit: Option<&str>, commit_time: Option<i64>, has_go_mod: bool) -> Option<String> {
+    let re = match Regex::new(r"^v([0-9]+)\.[0-9]+\.[0-9]+(-[0-9A-Za-z.-]+)?$") {
+        Ok(re) => re,
+        _ => return None,
+    };
+    if let Some(caps) = re.captures(version) {
+        let major = caps.get(1).map_or(0, |m| m.as_str().parse::<u64>().unwrap_or(0));
+        return Some(if major >= 2 && !has_go_mod {
+            format!("{}+incompatible", version)
+        } else {
+            version.to_owned()
+        })
+    }
+    match (commit, commit_time) {
+        (Some(commit), Some(time)) if commit.len() >= 12 => Some(pseudo_version(time, commit)),
+        _ => None,
+    }
+}
+
+pub fn pseudo_version(commit_time: i64, commit: &str) -> String {
+    format!("v0.0.0-{}-{}", utc_timestamp(commit_time), &commit[..12])
+}
+
+fn utc_timestamp(seconds: i64) -> String {
+    let days = seconds.div_euclid(86400);
+    let secs = seconds.rem_euclid(86400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+pub fn go_mod(module_path: &str, requires: &[Require]) -> String {
+    let mut contents = format!("module {}\n\ngo {}\n", module_path, GO_DIRECTIVE);
+    if !requires.is_empty() {
+        contents.push_str("\nrequire (\n");
+        for req in requires {
+            contents.push_str(format!("\t{} {}\n", req.path, req.version).as_str());
+        }
+        contents.push_str(")\n");
+    }
+    let replaces: Vec<String> = requires.iter().filter_map(|req| req.replace.as_ref().map(|replace| format!("\t{} => {}\n", req.path, replace_target(replace)))).collect();
+    if !replaces.is_empty() {
+        contents.push_str("\nreplace (\n");
+        for replace in replaces {
+            contents.push_str(replace.as_str());
+        }
+        contents.push_str(")\n");
+    }
+    contents
+}
+
+fn replace_target(replace: &Replace) -> String {
+    match *replace {
+        Replace::Module(ref path, ref version) => format!("{} {}", path, version),
+        Replace::Dir(ref dir) => dir.clone(),
+    }
+}
+
+pub fn modules_txt(requires: &[Require], packages: &[Vec<String>]) -> String {
+    let mut contents = String::new();
+    for (req, pkgs) in requires.iter().zip(packages) {
+        contents.push_str(format!("# {} {}", req.path, req.version).as_str());
+        if let Some(ref replace) = req.replace {
+            contents.push_str(format!(" => {}", replace_target(replace)).as_str());
+        }
+        contents.push_str("\n## explicit\n");
+        for pkg in pkgs {
+            contents.push_str(pkg.as_str());
+            contents.push('\n');
+        }
+    }
+    contents
+}
+
+pub fn packages(dir_path: &Path, module_path: &str) -> io::Result<Vec<String>> {
+    let mut pkgs = Vec::new();
+    collect_packages(dir_path, module_path, true, &mut pkgs)?;
+    pkgs.sort();
+    Ok(pkgs)
+}
+
+fn collect_packages(dir_path: &Path, pkg_path: &str, is_root: bool, pkgs: &mut Vec<String>) -> io::Result<()> {
+    if !is_root && dir_path.join("go.mod").is_file() {
+        return Ok(())
+    }
+    let mut has_go_files = false;
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if name.starts_with('.') || name.starts_with('_') || name == "testdata" || name == "vendor" {
+                continue
+            }
+            collect_packages(entry.path().as_path(), format!("{}/{}", pkg_path, name).as_str(), false, pkgs)?;
+        } else if file_type.is_file() && name.ends_with(".go") && !name.ends_with("_test.go") {
+            has_go_files = true;
+        }
+    }
+    if has_go_files {
+        pkgs.push(pkg_path.to_owned());
+    }
+    Ok(())
+}
+
+pub fn hash_module(dir_path: &Path, module_path: &str, version: &str) -> io::Result<String> {
+    let mut files = Vec::new();
+    collect_module_files(dir_path, String::new(), &mut files)?;
+
+    let mut named_files = Vec::new();
+    for rel_path in files {
+        let mut contents = Vec::new();
+        File::open(dir_path.join(rel_path.as_str()))?.read_to_end(&mut contents)?;
+        named_files.push((format!("{}@{}/{}", module_path, version, rel_path), contents));
+    }
+    Ok(hash_files(named_files))
+}
+
+// The `h1:` hash of Go over files named like the entries of the module zip
+pub fn hash_files(mut files: Vec<(String, Vec<u8>)>) -> String {
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut summary = String::new();
+    for (name, contents) in files {
+        summary.push_str(format!("{}  {}\n", hex_sha256(&contents), name).as_str());
+    }
+    format!("h1:{}", base64::encode(Sha256::digest(summary.as_bytes()).as_slice()))
+}
+
+pub fn hash_go_mod(contents: &[u8]) -> String {
+    let summary = format!("{}  go.mod\n", hex_sha256(contents));
+    format!("h1:{}", base64::encode(Sha256::digest(summary.as_bytes()).as_slice()))
+}
+
+fn hex_sha256(contents: &[u8]) -> String {
+    let mut hex = String::new();
+    for byte in Sha256::digest(contents) {
+        hex.push_str(format!("{:02x}", byte).as_str());
+    }
+    hex
+}
+
+// Follows the file selection of the Go module zip: no VCS directories, nested modules or vendored packages
+fn collect_module_files(dir_path: &Path, prefix: String, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel_path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if name == ".git" || name == ".hg" || name == ".svn" || name == ".bzr" || name == modproxy::META_DIR || entry.path().join("go.mod").is_file() {
+                continue
+            }
+            collect_module_files(entry.path().as_path(), rel_path, files)?;
+        } else if file_type.is_file() && !is_vendored_package(rel_path.as_str()) {
+            files.push(rel_path);
+        }
+    }
+    Ok(())
+}
+
+fn is_vendored_package(rel_path: &str) -> bool {
+    let start = if rel_path.starts_with("vendor/") {
+        "vendor/".len()
+    } else {
+        match rel_path.find("/vendor/") {
+            Some(index) => index + "/vendor/".len(),
+            None => return false,
+        }
+    };
+    rel_path[start..].contains('/')
+}
+
+pub fn module_path_from_repo(repo_url: &str) -> String {
+    let path = match repo_url.find("://") {
+        Some(index) => repo_url[index + 3..].to_owned(),
+        None => match (repo_url.find('@'), repo_url.find(':')) {
+            (Some(at), Some(colon)) if at < colon => format!("{}/{}", &repo_url[at + 1..colon], &repo_url[colon + 1..]),
+            _ => repo_url.to_owned(),
+        },
+    };
+    let path = match path.find('@') {
+        Some(index) if index < path.find('/').unwrap_or(path.len()) => path[index + 1..].to_owned(),
+        _ => path,
+    };
+    path.trim_end_matches('/').trim_end_matches(".git").to_owned()
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, BTreeSet};
+use inner::imports;
+use json::JsonValue;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub root: String,
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Graph {
+    pub fn new(root: &str) -> Self {
+        let mut edges = BTreeMap::new();
+        edges.insert(root.to_owned(), BTreeSet::new());
+        Graph {
+            root: root.to_owned(),
+            edges,
+        }
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str) {
+        self.edges.entry(to.to_owned()).or_default();
+        self.edges.entry(from.to_owned()).or_default().insert(to.to_owned());
+    }
+
+    pub fn paths(&self, import: &str) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        let mut path = vec![self.root.clone()];
+        self.collect_paths(import, &mut path, &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, import: &str, path: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+        let node = match path.last() {
+            Some(node) => node.clone(),
+            None => return,
+        };
+        if path.len() > 1 && imports::provides(node.as_str(), import) {
+            paths.push(path.clone());
+            return
+        }
+        let children = match self.edges.get(&node) {
+            Some(children) => children,
+            None => return,
+        };
+        for child in children {
+            if path.contains(child) {
+                continue
+            }
+            path.push(child.clone());
+            self.collect_paths(import, path, paths);
+            path.pop();
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for (node, children) in &self.edges {
+            if children.is_empty() {
+                dot.push_str(format!("\t{:?};\n", node).as_str());
+            }
+            for child in children {
+                dot.push_str(format!("\t{:?} -> {:?};\n", node, child).as_str());
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut nodes = array![];
+        let mut edges = array![];
+        for (node, children) in &self.edges {
+            let _ = nodes.push(node.as_str());
+            for child in children {
+                let _ = edges.push(object!{
+                    "from" => node.as_str(),
+                    "to" => child.as_str()
+                });
+            }
+        }
+        object!{
+            "root" => self.root.as_str(),
+            "nodes" => nodes,
+            "edges" => edges
+        }
+    }
+}
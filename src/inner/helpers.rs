@@ -1,16 +1,23 @@
-use std::fs;
+use std::{env, fs};
 use std::path::{Component, Path, PathBuf};
 use std::io::{self, Write};
 use threadpool::ThreadPool;
-use num_cpus;
 use regex::Regex;
-use inner::{git_helper, json_helper};
-use inner::vendor::VENDOR_DIR;
-use git2::Repository;
+use inner::{jobs, json_helper, vcs};
+use inner::context::Context;
+use inner::manifest::LoadError;
+use inner::vcs::Vcs;
 use json::JsonValue;
 use inner::logger::Logger;
-use curl::easy::Easy;
-use std::str;
+
+pub const OFFLINE_ENV: &str = "RUBIGO_OFFLINE";
+
+pub fn is_offline() -> bool {
+    match env::var(OFFLINE_ENV) {
+        Ok(value) => !value.is_empty() && value != "0" && value.to_lowercase() != "false",
+        Err(_) => false,
+    }
+}
 
 pub fn get_current_dir() -> String {
     match fs::canonicalize(Path::new(Component::CurDir.as_os_str())) {
@@ -45,13 +52,13 @@ pub fn confirmation_prompt(msg: &str) -> io::Result<bool> {
     }
 }
 
-pub fn version_prompt(repo: &Repository) -> Option<(String, String)> {
-    let latest_commit = match git_helper::get_latest_commit(repo) {
+pub fn version_prompt(repo: &dyn Vcs) -> Option<(String, String)> {
+    let latest_commit = match repo.latest_commit() {
         Some(ver) => ver,
         None => return None,
     };
-    let tag_version = git_helper::get_latest_version(repo, None);
-    let current_branch = git_helper::get_current_branch(repo);
+    let tag_version = vcs::latest_version(&repo.tags().unwrap_or_default(), None, &[]);
+    let current_branch = repo.current_branch();
 
     if tag_version.is_none() && current_branch.is_none() {
         return Some((latest_commit.clone(), latest_commit));
@@ -99,13 +106,8 @@ pub fn version_prompt(repo: &Repository) -> Option<(String, String)> {
     }
 }
 
-pub fn new_thread_pool() -> ThreadPool {
-    let threads_num = num_cpus::get();
-    ThreadPool::new(if threads_num > 1 {
-        threads_num
-    } else {
-        2
-    })
+pub fn new_thread_pool(ctx: &Context) -> Result<ThreadPool, String> {
+    Ok(ThreadPool::new(jobs::count(ctx.jobs, &ctx.config)?))
 }
 
 pub fn strip_url_scheme(pkg_import: &str) -> String {
@@ -116,8 +118,20 @@ pub fn strip_url_scheme(pkg_import: &str) -> String {
     re.replace_all(pkg_import, "").into_owned()
 }
 
-pub fn get_path_from_url(pkg_import: &str) -> PathBuf {
-    let mut pkg_path_buf = PathBuf::from(VENDOR_DIR);
+pub fn repo_root(pkg_import: &str) -> String {
+    let segments: Vec<&str> = pkg_import.split('/').filter(|s| !s.is_empty()).collect();
+    if let Some(index) = segments.iter().position(|s| s.ends_with(".git")) {
+        return segments[..index + 1].join("/")
+    }
+    let length = match segments.first() {
+        Some(&"gopkg.in") if segments.len() > 1 && segments[1].contains(".v") => 2,
+        _ => 3,
+    };
+    segments[..length.min(segments.len())].join("/")
+}
+
+pub fn get_path_from_url(pkg_import: &str, vendor_path: &Path) -> PathBuf {
+    let mut pkg_path_buf = vendor_path.to_path_buf();
     let path_segments = pkg_import.split("/");
     for segment in path_segments {
         pkg_path_buf.push(segment)
@@ -125,7 +139,18 @@ pub fn get_path_from_url(pkg_import: &str) -> PathBuf {
     pkg_path_buf
 }
 
-pub fn remove_diff_packages(old_lock: &JsonValue, new_lock: &JsonValue, logger: Logger) {
+pub fn unwrap_or_empty<T: Default>(result: Result<T, LoadError>, file_name: &str, logger: Logger) -> T {
+    match result {
+        Ok(content) => content,
+        Err(LoadError::Io(ref e)) if e.kind() != io::ErrorKind::InvalidData => T::default(),
+        Err(e) => {
+            logger.fatal(format!("unable to read `{}`: {}", file_name, e));
+            T::default()
+        },
+    }
+}
+
+pub fn remove_diff_packages(old_lock: &JsonValue, new_lock: &JsonValue, vendor_path: &Path, logger: Logger) {
     if old_lock.is_null() {
         return
     }
@@ -147,7 +172,7 @@ pub fn remove_diff_packages(old_lock: &JsonValue, new_lock: &JsonValue, logger:
                     continue 'outer;
                 }
             }
-            let _ = remove_package(old_pkg_name, logger);
+            let _ = remove_package(old_pkg_name, vendor_path, logger);
         }
     }
 
@@ -168,13 +193,13 @@ pub fn remove_diff_packages(old_lock: &JsonValue, new_lock: &JsonValue, logger:
                     continue 'outer2;
                 }
             }
-            let _ = remove_package(old_pkg_name, logger);
+            let _ = remove_package(old_pkg_name, vendor_path, logger);
         }
     }
 }
 
-pub fn remove_package(dir_path: &str, logger: Logger) -> bool {
-    let pkg_path_buf: PathBuf = get_path_from_url(dir_path);
+pub fn remove_package(dir_path: &str, vendor_path: &Path, logger: Logger) -> bool {
+    let pkg_path_buf: PathBuf = get_path_from_url(dir_path, vendor_path);
     let pkg_path = pkg_path_buf.as_path();
     match fs::remove_dir_all(pkg_path) {
         Ok(_) => {
@@ -194,61 +219,3 @@ pub fn remove_package(dir_path: &str, logger: Logger) -> bool {
     }
     true
 }
-
-pub fn modify_golang_org(repo_url: &str) -> (String, Option<String>) {
-    if repo_url.starts_with("golang.org/x") {
-        let mut buf = String::new();
-        {
-            let mut handle = Easy::new();
-            match handle.url(repo_url) {
-                Ok(_) => (),
-                _ => return (format!("http://{}", repo_url), None),
-            };
-            let mut transfer = handle.transfer();
-            match transfer.write_function(|data| {
-                match str::from_utf8(data) {
-                    Ok(s) => {
-                        buf.push_str(s);
-                        Ok(data.len())
-                    },
-                    _ => Ok(0),
-                }
-            }) {
-                Ok(_) => (),
-                _ => return (format!("http://{}", repo_url), None),
-            };
-            match transfer.perform() {
-                Ok(_) => (),
-                _ => return (format!("http://{}", repo_url), None),
-            };
-        }
-        let re = match Regex::new(r#".*go-import.* git ([^'"]*)"?'?>"#) {
-            Ok(r) => r,
-            _ => return (format!("http://{}", repo_url), None),
-        };
-        let cap = match re.captures(buf.as_str()) {
-            Some(c) => c,
-            None => return (format!("http://{}", repo_url), None),
-        };
-        return match cap.get(1) {
-            Some(s) => {
-                let url = s.as_str();
-                let re = match Regex::new(r#"[^/]*//[^/]*/(.*)"#) {
-                    Ok(r) => r,
-                    _ => return (url.to_owned(), None),
-                };
-                let cap = match re.captures(url) {
-                    Some(c) => c,
-                    None => return (url.to_owned(), None),
-                };
-                match cap.get(1) {
-                    Some(p) => return (url.to_owned(), Some(format!("golang.org/x/{}", p.as_str()))),
-                    None => (),
-                };
-                (url.to_owned(), None)
-            },
-            _ => (format!("http://{}", repo_url), None),
-        }
-    }
-    (format!("http://{}", repo_url), None)
-}
@@ -0,0 +1,52 @@
+use curl::easy::Easy;
+use curl;
+use json::JsonValue;
+use inner::jobs;
+use inner::network::{self, Failure, Settings};
+
+// Fails unless the server responds with a success status, a `file://` url has no status
+pub fn get(url: &str, config: &JsonValue) -> Result<Vec<u8>, String> {
+    let settings = network::settings(config)?;
+    network::retry(&settings, || {
+        let _permit = jobs::acquire(url, config).map_err(Failure::permanent)?;
+        get_once(url, &settings)
+    })
+}
+
+fn get_once(url: &str, settings: &Settings) -> Result<Vec<u8>, Failure> {
+    let mut buf = Vec::new();
+    let mut handle = Easy::new();
+    let setup = |e: curl::Error| Failure::permanent(e.to_string());
+    handle.url(url).map_err(setup)?;
+    handle.follow_location(true).map_err(setup)?;
+    handle.connect_timeout(settings.connect_timeout).map_err(setup)?;
+    // The transfer is aborted if it does not receive a byte for the whole `timeout`
+    if settings.timeout.as_secs() > 0 {
+        handle.low_speed_limit(1).map_err(setup)?;
+        handle.low_speed_time(settings.timeout).map_err(setup)?;
+    }
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|data| {
+            buf.extend_from_slice(data);
+            Ok(data.len())
+        }).map_err(setup)?;
+        if let Err(e) = transfer.perform() {
+            let message = format!("unable to get `{}`: {}", url, e);
+            return Err(if is_transient(&e) {
+                Failure::transient(message)
+            } else {
+                Failure::permanent(message)
+            })
+        }
+    }
+    match handle.response_code().map_err(setup)? {
+        0 | 200..=299 => Ok(buf),
+        code if network::is_transient_status(code) => Err(Failure::transient(format!("unable to get `{}`: status {}", url, code))),
+        code => Err(Failure::permanent(format!("unable to get `{}`: status {}", url, code))),
+    }
+}
+
+fn is_transient(e: &curl::Error) -> bool {
+    e.is_couldnt_resolve_host() || e.is_couldnt_connect() || e.is_operation_timedout() || e.is_partial_file() || e.is_got_nothing() || e.is_send_error() || e.is_recv_error() || e.is_again()
+}
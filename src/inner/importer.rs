@@ -0,0 +1,464 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use json::{self, JsonValue};
+use toml;
+use yaml_rust::{Yaml, YamlLoader};
+use regex::Regex;
+use inner::helpers;
+use inner::manifest::{Manifest, GitPackage, LockedPackage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Dep,
+    Glide,
+    Govendor,
+    Godep,
+    Gomod,
+}
+
+pub const TOOLS: &[Tool] = &[Tool::Dep, Tool::Glide, Tool::Govendor, Tool::Godep, Tool::Gomod];
+pub const DEPENDENCY_FILES: &[&str] = &["rubigo.json", "Gopkg.toml", "Gopkg.lock", "glide.yaml", "glide.lock", "vendor/vendor.json", "Godeps/Godeps.json", "go.mod"];
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tool::Dep => "dep",
+            Tool::Glide => "glide",
+            Tool::Govendor => "govendor",
+            Tool::Godep => "godep",
+            Tool::Gomod => "gomod",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tool> {
+        TOOLS.iter().cloned().find(|tool| tool.name() == name)
+    }
+
+    pub fn manifest_file(&self) -> &'static str {
+        match *self {
+            Tool::Dep => "Gopkg.toml",
+            Tool::Glide => "glide.yaml",
+            Tool::Govendor => "vendor/vendor.json",
+            Tool::Godep => "Godeps/Godeps.json",
+            Tool::Gomod => "go.mod",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub project_import: Option<String>,
+    pub git: Vec<GitPackage>,
+    pub lock: Vec<LockedPackage>,
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Imported {
+    fn add(&mut self, import: String, repo: Option<String>, version: String, locked: Option<(String, String)>) {
+        if self.git.iter().any(|pkg| pkg.import == import) {
+            return
+        }
+        self.git.push(GitPackage {
+            import: import.clone(),
+            repo: repo.clone(),
+            vcs: None,
+            version,
+            exclude: Vec::new(),
+        });
+        if let Some((locked_version, revision)) = locked {
+            self.lock.push(LockedPackage {
+                import,
+                repo,
+                url: None,
+                vcs: None,
+                version: locked_version,
+                exclude: Vec::new(),
+                commit: if is_full_sha(revision.as_str()) {
+                    Some(revision)
+                } else {
+                    None
+                },
+                hash: None,
+                sum: None,
+                parent: None,
+            });
+        }
+    }
+
+    fn skip(&mut self, import: String) {
+        if !self.git.iter().any(|pkg| pkg.import == import) && !self.skipped.contains(&import) {
+            self.skipped.push(import);
+        }
+    }
+}
+
+pub fn detect(dir_path: &Path) -> Option<Tool> {
+    TOOLS.iter().cloned().find(|tool| dir_path.join(tool.manifest_file()).is_file())
+}
+
+pub fn dependencies(dir_path: &Path) -> io::Result<Vec<GitPackage>> {
+    let manifest_path = dir_path.join("rubigo.json");
+    if manifest_path.is_file() {
+        return match Manifest::load(manifest_path.as_path()) {
+            Ok(manifest) => Ok(manifest.packages.git),
+            Err(e) => Err(e.into()),
+        }
+    }
+    match detect(dir_path) {
+        Some(tool) => Ok(import(tool, dir_path)?.git),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn import(tool: Tool, dir_path: &Path) -> io::Result<Imported> {
+    match tool {
+        Tool::Dep => import_dep(dir_path),
+        Tool::Glide => import_glide(dir_path),
+        Tool::Govendor => import_govendor(dir_path),
+        Tool::Godep => import_godep(dir_path),
+        Tool::Gomod => import_gomod(dir_path),
+    }
+}
+
+fn import_dep(dir_path: &Path) -> io::Result<Imported> {
+    let manifest = read_toml(dir_path.join("Gopkg.toml").as_path())?;
+    let lock = if dir_path.join("Gopkg.lock").is_file() {
+        read_toml(dir_path.join("Gopkg.lock").as_path())?
+    } else {
+        toml::Value::Table(toml::value::Table::new())
+    };
+
+    let mut constraints = Vec::new();
+    for section in &["override", "constraint"] {
+        if let Some(entries) = manifest.get(section).and_then(|v| v.as_array()) {
+            for entry in entries {
+                let name = match toml_str(entry, "name") {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let version = match toml_str(entry, "version") {
+                    Some(version) => Some(dep_constraint(version.as_str())),
+                    None => toml_str(entry, "branch").or_else(|| toml_str(entry, "revision")),
+                };
+                constraints.push((name, version, toml_str(entry, "source")));
+            }
+        }
+    }
+
+    let mut imported = Imported::default();
+    let projects = lock.get("projects").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    for project in &projects {
+        let name = match toml_str(project, "name") {
+            Some(name) => name,
+            None => continue,
+        };
+        let revision = match toml_str(project, "revision") {
+            Some(revision) => revision,
+            None => continue,
+        };
+        let locked_version = toml_str(project, "version").or_else(|| toml_str(project, "branch")).unwrap_or_else(|| revision.clone());
+        let (version, source) = match constraints.iter().find(|c| c.0 == name) {
+            Some((_, version, source)) => (version.clone().unwrap_or_else(|| locked_version.clone()), source.clone().or_else(|| toml_str(project, "source"))),
+            None => (locked_version.clone(), toml_str(project, "source")),
+        };
+        imported.add(name, source.map(|s| source_url(s.as_str())), version, Some((locked_version, revision)));
+    }
+
+    for (name, version, source) in constraints {
+        match version {
+            Some(version) => imported.add(name, source.map(|s| source_url(s.as_str())), version, None),
+            None => imported.skip(name),
+        }
+    }
+    Ok(imported)
+}
+
+fn import_glide(dir_path: &Path) -> io::Result<Imported> {
+    let manifest = read_yaml(dir_path.join("glide.yaml").as_path())?;
+    let lock = if dir_path.join("glide.lock").is_file() {
+        read_yaml(dir_path.join("glide.lock").as_path())?
+    } else {
+        Yaml::Null
+    };
+
+    let mut imported = Imported {
+        project_import: manifest["package"].as_str().map(|s| s.to_owned()),
+        ..Imported::default()
+    };
+
+    let mut constraints = Vec::new();
+    for section in &["import", "testImport"] {
+        if let Some(entries) = manifest[*section].as_vec() {
+            for entry in entries {
+                let name = match entry["package"].as_str() {
+                    Some(name) => name.to_owned(),
+                    None => continue,
+                };
+                constraints.push((name, entry["version"].as_str().map(|s| s.to_owned()), entry["repo"].as_str().map(|s| s.to_owned())));
+            }
+        }
+    }
+
+    for section in &["imports", "testImports"] {
+        if let Some(entries) = lock[*section].as_vec() {
+            for entry in entries {
+                let name = match entry["name"].as_str() {
+                    Some(name) => name.to_owned(),
+                    None => continue,
+                };
+                let revision = match entry["version"].as_str() {
+                    Some(version) => version.to_owned(),
+                    None => continue,
+                };
+                let (version, repo) = match constraints.iter().find(|c| c.0 == name) {
+                    Some((_, version, repo)) => (version.clone().unwrap_or_else(|| revision.clone()), repo.clone().or_else(|| entry["repo"].as_str().map(|s| s.to_owned()))),
+                    None => (revision.clone(), entry["repo"].as_str().map(|s| s.to_owned())),
+                };
+                imported.add(name, repo, version, Some((revision.clone(), revision)));
+            }
+        }
+    }
+
+    for (name, version, repo) in constraints {
+        match version {
+            Some(version) => imported.add(name, repo, version, None),
+            None => imported.skip(name),
+        }
+    }
+    Ok(imported)
+}
+
+fn import_govendor(dir_path: &Path) -> io::Result<Imported> {
+    let manifest = read_json(dir_path.join("vendor").join("vendor.json").as_path())?;
+
+    let mut imported = Imported {
+        project_import: manifest["rootPath"].as_str().map(|s| s.to_owned()),
+        ..Imported::default()
+    };
+
+    for pkg in manifest["package"].members() {
+        let import = match pkg["path"].as_str() {
+            Some(path) => helpers::repo_root(path),
+            None => continue,
+        };
+        let revision = match pkg["revision"].as_str() {
+            Some(revision) => revision.to_owned(),
+            None => continue,
+        };
+        let version = match pkg["version"].as_str() {
+            Some(version) if is_version(version) => format!("^{}", version.trim_start_matches('v')),
+            Some(version) if !version.is_empty() => version.to_owned(),
+            _ => revision.clone(),
+        };
+        let locked_version = match pkg["versionExact"].as_str() {
+            Some(version) if !version.is_empty() => version.to_owned(),
+            _ => revision.clone(),
+        };
+        let repo = pkg["origin"].as_str().map(|origin| format!("https://{}", helpers::repo_root(origin)));
+        imported.add(import, repo, version, Some((locked_version, revision)));
+    }
+    Ok(imported)
+}
+
+fn import_godep(dir_path: &Path) -> io::Result<Imported> {
+    let manifest = read_json(dir_path.join("Godeps").join("Godeps.json").as_path())?;
+
+    let mut imported = Imported {
+        project_import: manifest["ImportPath"].as_str().map(|s| s.to_owned()),
+        ..Imported::default()
+    };
+
+    for dep in manifest["Deps"].members() {
+        let import = match dep["ImportPath"].as_str() {
+            Some(path) => helpers::repo_root(path),
+            None => continue,
+        };
+        let revision = match dep["Rev"].as_str() {
+            Some(revision) => revision.to_owned(),
+            None => continue,
+        };
+        imported.add(import, None, revision.clone(), Some((revision.clone(), revision)));
+    }
+    Ok(imported)
+}
+
+fn import_gomod(dir_path: &Path) -> io::Result<Imported> {
+    let file_path = dir_path.join("go.mod");
+    let mut imported = Imported::default();
+    let mut requires = Vec::new();
+    let mut replaces = Vec::new();
+    let mut excludes = Vec::new();
+
+    let mut block: Option<String> = None;
+    for (index, raw_line) in read_to_string(file_path.as_path())?.lines().enumerate() {
+        let line = match raw_line.find("//") {
+            Some(pos) => &raw_line[..pos],
+            None => raw_line,
+        };
+        let mut tokens: Vec<String> = line.split_whitespace().map(|token| token.trim_matches('"').to_owned()).collect();
+        if tokens.is_empty() {
+            continue
+        }
+        let directive = match block {
+            Some(ref directive) => {
+                if tokens[0] == ")" {
+                    block = None;
+                    continue
+                }
+                directive.clone()
+            },
+            None => {
+                let directive = tokens.remove(0);
+                if tokens.len() == 1 && tokens[0] == "(" {
+                    block = Some(directive);
+                    continue
+                }
+                directive
+            },
+        };
+        let invalid = || invalid_data(file_path.as_path(), format!("line {}: invalid `{}` directive", index + 1, directive));
+        match directive.as_str() {
+            "module" => imported.project_import = tokens.first().cloned(),
+            "require" => match tokens.len() {
+                2 => requires.push((tokens[0].clone(), tokens[1].clone())),
+                _ => return Err(invalid()),
+            },
+            "exclude" => match tokens.len() {
+                2 => excludes.push((tokens[0].clone(), tokens[1].clone())),
+                _ => return Err(invalid()),
+            },
+            "replace" => {
+                let arrow = match tokens.iter().position(|token| token == "=>") {
+                    Some(arrow) if (arrow == 1 || arrow == 2) && (tokens.len() == arrow + 2 || tokens.len() == arrow + 3) => arrow,
+                    _ => return Err(invalid()),
+                };
+                replaces.push((tokens[0].clone(), tokens.get(1).filter(|_| arrow == 2).cloned(), tokens[arrow + 1].clone(), tokens.get(arrow + 2).cloned()));
+            },
+            _ => (),
+        }
+    }
+
+    for (path, required_version) in requires {
+        let mut repo = module_repo(path.as_str());
+        let mut version = gomod_constraint(required_version.as_str());
+        let replace = replaces.iter().find(|r| r.0 == path && r.1.as_ref().map_or(true, |v| *v == required_version));
+        if let Some((_, _, new_path, new_version)) = replace {
+            match new_version {
+                Some(new_version) => {
+                    repo = Some(format!("https://{}", strip_major_suffix(new_path.as_str())));
+                    version = gomod_constraint(new_version.as_str());
+                },
+                None => imported.warnings.push(format!("the package `{}` is replaced by the directory `{}` which is not supported, the required version is used", path, new_path)),
+            }
+        }
+        let exclude: Vec<String> = excludes.iter().filter(|e| e.0 == path).map(|e| e.1.clone()).collect();
+        imported.add(path.clone(), repo, version, None);
+        if let Some(pkg) = imported.git.iter_mut().find(|pkg| pkg.import == path) {
+            pkg.exclude = exclude;
+        }
+    }
+    Ok(imported)
+}
+
+fn read_to_string(file_path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(file_path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+fn invalid_data<T: ::std::fmt::Display>(file_path: &Path, err: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unable to parse `{}`: {}", file_path.to_str().unwrap_or("unknown"), err))
+}
+
+fn read_toml(file_path: &Path) -> io::Result<toml::Value> {
+    read_to_string(file_path)?.parse::<toml::Value>().map_err(|e| invalid_data(file_path, e))
+}
+
+fn read_yaml(file_path: &Path) -> io::Result<Yaml> {
+    match YamlLoader::load_from_str(read_to_string(file_path)?.as_str()) {
+        Ok(mut docs) => if docs.is_empty() {
+            Ok(Yaml::Null)
+        } else {
+            Ok(docs.remove(0))
+        },
+        Err(e) => Err(invalid_data(file_path, e)),
+    }
+}
+
+fn read_json(file_path: &Path) -> io::Result<JsonValue> {
+    json::parse(read_to_string(file_path)?.as_str()).map_err(|e| invalid_data(file_path, e))
+}
+
+fn toml_str(value: &toml::Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(|s| s.to_owned())
+}
+
+fn source_url(source: &str) -> String {
+    if source.contains("://") || source.contains('@') {
+        source.to_owned()
+    } else {
+        format!("https://{}", source)
+    }
+}
+
+fn is_full_sha(revision: &str) -> bool {
+    revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_version(version: &str) -> bool {
+    match Regex::new(r"^v?[0-9]+(\.[0-9]+){0,2}$") {
+        Ok(re) => re.is_match(version),
+        _ => false,
+    }
+}
+
+// Modules of major version 2 or later live in the repository without the `/vN` suffix
+fn strip_major_suffix(path: &str) -> String {
+    if path.starts_with("gopkg.in/") {
+        return path.to_owned()
+    }
+    match Regex::new(r"^(.+)/v[0-9]+$") {
+        Ok(re) => match re.captures(path) {
+            Some(caps) => caps[1].to_owned(),
+            None => path.to_owned(),
+        },
+        _ => path.to_owned(),
+    }
+}
+
+fn module_repo(path: &str) -> Option<String> {
+    let repo_path = strip_major_suffix(path);
+    if repo_path == path {
+        None
+    } else {
+        Some(format!("https://{}", repo_path))
+    }
+}
+
+// Pseudo-versions pin a commit, other versions become caret constraints
+fn gomod_constraint(version: &str) -> String {
+    let version = version.trim_end_matches("+incompatible");
+    if let Ok(re) = Regex::new(r"^v[0-9]+\.[0-9]+\.[0-9]+-(?:[0-9A-Za-z.]+\.)?[0-9]{14}-([0-9a-f]{12})$") {
+        if let Some(caps) = re.captures(version) {
+            return caps[1].to_owned()
+        }
+    }
+    match version.strip_prefix('v') {
+        Some(stripped) => format!("^{}", stripped),
+        None => version.to_owned(),
+    }
+}
+
+// dep treats a bare version as a caret range and accepts a `v` prefix after operators
+fn dep_constraint(version: &str) -> String {
+    let version = version.trim();
+    if is_version(version) {
+        return format!("^{}", version.trim_start_matches('v'))
+    }
+    match Regex::new(r"([=<>~^]\s*)v([0-9])") {
+        Ok(re) => re.replace_all(version, "$1$2").into_owned(),
+        _ => version.to_owned(),
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+pub fn scan_dir(dir_path: &Path) -> io::Result<BTreeSet<String>> {
+    let mut imports = BTreeSet::new();
+    collect_imports(dir_path, true, &mut imports)?;
+    Ok(imports)
+}
+
+fn collect_imports(dir_path: &Path, is_root: bool, imports: &mut BTreeSet<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if name.starts_with('.') || name.starts_with('_') || name == "testdata" || (is_root && name == "vendor") {
+                continue
+            }
+            collect_imports(entry.path().as_path(), false, imports)?;
+        } else if file_type.is_file() && name.ends_with(".go") {
+            let mut contents = String::new();
+            File::open(entry.path())?.read_to_string(&mut contents)?;
+            imports.extend(parse_imports(contents.as_str()));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break
+                    }
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break
+                    }
+                    last = c;
+                }
+            },
+            '"' | '`' => {
+                let mut text = String::new();
+                while let Some(t) = chars.next() {
+                    if t == c {
+                        break
+                    }
+                    if t == '\\' && c == '"' {
+                        if let Some(escaped) = chars.next() {
+                            text.push(escaped);
+                        }
+                        continue
+                    }
+                    text.push(t);
+                }
+                tokens.push(Token::Text(text));
+            },
+            '\'' => {
+                while let Some(t) = chars.next() {
+                    if t == '\\' {
+                        chars.next();
+                    } else if t == '\'' {
+                        break
+                    }
+                }
+            },
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&w) = chars.peek() {
+                    if !w.is_alphanumeric() && w != '_' {
+                        break
+                    }
+                    word.push(w);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            },
+            c if c.is_whitespace() => (),
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    tokens
+}
+
+// Import declarations must precede the other top level declarations of a Go file
+pub fn parse_imports(source: &str) -> Vec<String> {
+    let tokens = tokenize(source);
+    let mut imports = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        match tokens[index] {
+            Token::Word(ref word) if word == "import" => {
+                index += 1;
+                if tokens.get(index) == Some(&Token::Symbol('(')) {
+                    while index < tokens.len() && tokens[index] != Token::Symbol(')') {
+                        if let Token::Text(ref path) = tokens[index] {
+                            imports.push(path.clone());
+                        }
+                        index += 1;
+                    }
+                } else {
+                    while index < tokens.len() {
+                        if let Token::Text(ref path) = tokens[index] {
+                            imports.push(path.clone());
+                            break
+                        }
+                        index += 1;
+                    }
+                }
+            },
+            Token::Word(ref word) if word == "func" || word == "var" || word == "const" || word == "type" => break,
+            _ => (),
+        }
+        index += 1;
+    }
+    imports
+}
+
+pub fn is_standard(import: &str) -> bool {
+    match import.split('/').next() {
+        Some(first) => !first.contains('.'),
+        None => true,
+    }
+}
+
+pub fn provides(pkg_import: &str, import: &str) -> bool {
+    import == pkg_import || (import.starts_with(pkg_import) && import[pkg_import.len()..].starts_with('/'))
+}
@@ -0,0 +1,61 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use sha2::{Sha256, Digest};
+use inner::modproxy;
+
+pub const HASH_PREFIX: &str = "sha256-";
+
+pub fn hash_dir(dir_path: &Path) -> io::Result<String> {
+    let mut entries = Vec::new();
+    collect_entries(dir_path, String::new(), &mut entries)?;
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    let mut buf = Vec::new();
+    for rel_path in entries {
+        let path = dir_path.join(rel_path.as_str());
+        let metadata = fs::symlink_metadata(&path)?;
+        buf.clear();
+        if metadata.file_type().is_symlink() {
+            hasher.update(b"link\0");
+            buf.extend_from_slice(fs::read_link(&path)?.to_string_lossy().replace('\\', "/").as_bytes());
+        } else {
+            hasher.update(b"file\0");
+            File::open(&path)?.read_to_end(&mut buf)?;
+        }
+        hasher.update(rel_path.as_bytes());
+        hasher.update(format!("\0{}\0", buf.len()).as_bytes());
+        hasher.update(&buf);
+    }
+
+    let mut hash = String::from(HASH_PREFIX);
+    for byte in hasher.finalize() {
+        hash.push_str(format!("{:02x}", byte).as_str());
+    }
+    Ok(hash)
+}
+
+fn collect_entries(dir_path: &Path, prefix: String, entries: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(name) => name.to_string_lossy().into_owned(),
+        };
+        if name == ".git" || name == ".hg" || name == modproxy::META_DIR {
+            continue
+        }
+        let rel_path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        if entry.file_type()?.is_dir() {
+            collect_entries(entry.path().as_path(), rel_path, entries)?;
+        } else {
+            entries.push(rel_path);
+        }
+    }
+    Ok(())
+}
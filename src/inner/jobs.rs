@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Condvar, Mutex};
+use json::JsonValue;
+use num_cpus;
+use inner::network;
+
+pub const JOBS_ENV: &str = "RUBIGO_JOBS";
+pub const JOBS_KEY: &str = "jobs";
+pub const HOST_JOBS_KEY: &str = "host_jobs";
+
+// `jobs` of `--jobs` or `RUBIGO_JOBS` wins over the `jobs` entry of the configuration, which wins over the number of CPUs
+pub fn count(jobs: Option<usize>, config: &JsonValue) -> Result<usize, String> {
+    if let Some(jobs) = jobs {
+        return Ok(jobs)
+    }
+    match config[JOBS_KEY] {
+        JsonValue::Null => Ok(default_count()),
+        ref jobs => positive(jobs).ok_or_else(|| format!("`{}` should be a positive integer, found `{}`", JOBS_KEY, jobs)),
+    }
+}
+
+pub fn parse_count(jobs: &str) -> Option<usize> {
+    jobs.trim().parse().ok().filter(|jobs| *jobs > 0)
+}
+
+// The number of jobs set by `RUBIGO_JOBS`, if any
+pub fn env_count() -> Result<Option<usize>, String> {
+    match env::var(JOBS_ENV).ok().filter(|jobs| !jobs.is_empty()) {
+        Some(jobs) => parse_count(jobs.as_str()).map(Some).ok_or_else(|| format!("`{}` should be a positive integer, found `{}`", JOBS_ENV, jobs)),
+        None => Ok(None),
+    }
+}
+
+pub fn default_count() -> usize {
+    num_cpus::get().max(2)
+}
+
+// The `jobs` of a host in `hosts` wins over `host_jobs`, a host has no limit other than the number of jobs without them
+pub fn host_limit(config: &JsonValue, host: &str) -> Result<Option<usize>, String> {
+    match (&config["hosts"][host][JOBS_KEY], &config[HOST_JOBS_KEY]) {
+        (&JsonValue::Null, &JsonValue::Null) => Ok(None),
+        (&JsonValue::Null, jobs) => positive(jobs).map(Some).ok_or_else(|| format!("`{}` should be a positive integer, found `{}`", HOST_JOBS_KEY, jobs)),
+        (jobs, _) => positive(jobs).map(Some).ok_or_else(|| format!("`hosts.{}.{}` should be a positive integer, found `{}`", host, JOBS_KEY, jobs)),
+    }
+}
+
+fn positive(value: &JsonValue) -> Option<usize> {
+    value.as_usize().filter(|jobs| *jobs > 0)
+}
+
+struct Hosts {
+    running: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+lazy_static! {
+    static ref HOSTS: Hosts = Hosts {
+        running: Mutex::new(HashMap::new()),
+        released: Condvar::new(),
+    };
+}
+
+fn hosts() -> &'static Hosts {
+    &HOSTS
+}
+
+// Holds one of the slots of a host until it is dropped
+pub struct Permit {
+    host: Option<String>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(ref host) = self.host {
+            let hosts = hosts();
+            let mut running = hosts.running.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(count) = running.get_mut(host) {
+                *count = count.saturating_sub(1);
+            }
+            hosts.released.notify_all();
+        }
+    }
+}
+
+// Waits until fewer than the limit of the host of `url` are transferring from it, local paths are not limited
+pub fn acquire(url: &str, config: &JsonValue) -> Result<Permit, String> {
+    match network::endpoint(url) {
+        Some((host, _)) => acquire_host(host.as_str(), config),
+        None => Ok(Permit { host: None }),
+    }
+}
+
+fn acquire_host(host: &str, config: &JsonValue) -> Result<Permit, String> {
+    let limit = match host_limit(config, host)? {
+        Some(limit) => limit,
+        None => return Ok(Permit { host: None }),
+    };
+    let hosts = hosts();
+    let mut running = hosts.running.lock().unwrap_or_else(|e| e.into_inner());
+    while running.get(host).map_or(false, |count| *count >= limit) {
+        running = hosts.released.wait(running).unwrap_or_else(|e| e.into_inner());
+    }
+    *running.entry(host.to_owned()).or_insert(0) += 1;
+    Ok(Permit {
+        host: Some(host.to_owned()),
+    })
+}
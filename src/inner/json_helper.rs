@@ -1,12 +1,20 @@
 use std::fs::File;
 use std::io::Write;
-use json::{self, JsonValue};
+use json::JsonValue;
 use std::path::Path;
-use std::io::{self, Read};
+use std::io;
+use inner::manifest::{Manifest, Lock};
 
 pub const IMPORT_KEY: &'static str = "import";
 pub const VERSION_KEY: &'static str = "version";
 pub const REPO_KEY: &'static str = "repo";
+pub const URL_KEY: &'static str = "url";
+pub const VCS_KEY: &'static str = "vcs";
+pub const HASH_KEY: &'static str = "hash";
+pub const SUM_KEY: &'static str = "sum";
+pub const COMMIT_KEY: &'static str = "commit";
+pub const EXCLUDE_KEY: &'static str = "exclude";
+pub const PARENT_KEY: &'static str = "parent";
 
 pub const INFO_KEY: &'static str = "info";
 pub const NAME_KEY: &'static str = "name";
@@ -49,16 +57,31 @@ pub fn write<P: AsRef<Path>>(json_path: P, project_name: &str, data: Option<Json
     Ok(())
 }
 
-pub fn read(json_path: &Path) -> io::Result<JsonValue> {
-    let mut file = File::open(json_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    match json::parse(contents.as_str()) {
-        Ok(content) => Ok(content),
-        Err(_) => Err(io::Error::new(io::ErrorKind::Other, format!("unable to parse json file: {:?}", json_path.to_str().unwrap_or("unknown")).as_str())),
+pub fn read_manifest(json_path: &Path) -> io::Result<JsonValue> {
+    match Manifest::load(json_path) {
+        Ok(manifest) => Ok(manifest.to_json()),
+        Err(e) => Err(e.into()),
     }
 }
 
+pub fn read_lock(lock_path: &Path) -> io::Result<JsonValue> {
+    match Lock::load(lock_path) {
+        Ok(lock) => Ok(lock.to_json()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn strip_lock_keys(git_packages: &JsonValue) -> JsonValue {
+    let mut packages = git_packages.clone();
+    for pkg in packages.members_mut() {
+        pkg.remove(COMMIT_KEY);
+        pkg.remove(HASH_KEY);
+        pkg.remove(SUM_KEY);
+        pkg.remove(PARENT_KEY);
+    }
+    packages
+}
+
 pub fn remove_package_from_array(pkg_import: &str, json_array: &JsonValue, is_str_array: bool) -> JsonValue {
     let mut result_array = json_array.clone();
     for i in 0..json_array.len() {
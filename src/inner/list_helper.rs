@@ -21,6 +21,11 @@ pub fn print_git_packages(pkgs: &JsonValue) {
             None => (),
         }
 
+        match pkg[json_helper::PARENT_KEY].as_str() {
+            Some(text) => println!("\t{}: {}", "Parent", text),
+            None => (),
+        }
+
         match pkg[json_helper::REPO_KEY].as_str() {
             Some(text) => println!("\t{}: {}\n", "Repository", text),
             None => println!(),
@@ -37,3 +42,24 @@ pub fn print_str_packages(pkgs: &JsonValue) {
         }
     }
 }
+
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    print!("{}", format_table(headers, rows));
+}
+
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header_cells: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    let mut table = String::new();
+    for row in Some(&header_cells).into_iter().chain(rows) {
+        let line: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:1$}", cell, width)).collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
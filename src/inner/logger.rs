@@ -1,8 +1,18 @@
 extern crate time;
 
+use std::cell::Cell;
+use std::env;
 use std::process;
 use std::fmt::Display;
 use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use atty;
+
+// A live display is redrawn at most this often, otherwise a task prints a plain line at this interval
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const PLAIN_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_COLUMNS: usize = 80;
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum Verbosity {
@@ -25,14 +35,27 @@ impl Logger {
 
     pub fn verbose<T: Display>(&self, title: &str, msg: T) {
         if self.verbosity == Verbosity::High {
-            println!("[{}] {} {}", time::strftime("%T", &time::now()).unwrap_or(String::from("00:00:00")), title, msg);
-            let _ = io::stdout().flush();
+            above_progress(|| {
+                println!("[{}] {} {}", timestamp(), title, msg);
+                let _ = io::stdout().flush();
+            });
+        }
+    }
+
+    // Unlike verbose messages, it is printed without `--verbose` and to the standard error
+    pub fn info<T: Display>(&self, title: &str, msg: T) {
+        if self.verbosity != Verbosity::None {
+            above_progress(|| {
+                let _ = writeln!(&mut io::stderr(), "{} {}", title, msg);
+            });
         }
     }
 
     pub fn error<T: Display>(&self, err: T) {
         if self.verbosity != Verbosity::None {
-            let _ = writeln!(&mut io::stderr(), "{} {}", "error:", err);
+            above_progress(|| {
+                let _ = writeln!(&mut io::stderr(), "{} {}", "error:", err);
+            });
         }
     }
 
@@ -40,4 +63,209 @@ impl Logger {
         self.error(err);
         process::exit(1);
     }
+
+    // The progress reported by the current thread is shown under `label` until the task is dropped
+    pub fn task(&self, label: &str) -> Task {
+        let id = if self.verbosity == Verbosity::None {
+            None
+        } else {
+            let mut board = board();
+            board.next_id += 1;
+            let id = board.next_id;
+            board.tasks.push(Line {
+                id,
+                label: label.to_owned(),
+                status: None,
+                printed_at: Instant::now(),
+            });
+            Some(id)
+        };
+        Task {
+            id,
+            previous: CURRENT_TASK.with(|current| current.replace(id)),
+        }
+    }
+
+    // Shows how many of `total` tasks are done above the lines of the running tasks
+    pub fn batch(&self, title: &str, total: usize) -> Batch {
+        if self.verbosity != Verbosity::None {
+            board().summary = Some((title.to_owned(), 0, total));
+        }
+        Batch {
+            is_shown: self.verbosity != Verbosity::None,
+        }
+    }
+}
+
+pub struct Task {
+    id: Option<usize>,
+    previous: Option<usize>,
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        CURRENT_TASK.with(|current| current.set(self.previous));
+        if let Some(id) = self.id {
+            let mut board = board();
+            board.tasks.retain(|line| line.id != id);
+            board.draw(true);
+        }
+    }
+}
+
+pub struct Batch {
+    is_shown: bool,
+}
+
+impl Batch {
+    pub fn done(&self) {
+        if self.is_shown {
+            let mut board = board();
+            if let Some((_, ref mut done, _)) = board.summary {
+                *done += 1;
+            }
+            board.draw(true);
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        if self.is_shown {
+            let mut board = board();
+            board.summary = None;
+            board.draw(true);
+        }
+    }
+}
+
+// The task of the current thread, which a helper thread could report its progress under
+pub fn current_task() -> Option<usize> {
+    CURRENT_TASK.with(|current| current.get())
+}
+
+// Reports the progress of the current thread under the task of another thread
+pub fn join_task(id: Option<usize>) {
+    CURRENT_TASK.with(|current| current.set(id));
+}
+
+// Updates the status of the task of the current thread, it is ignored outside of a task
+pub fn progress(status: String) {
+    let id = match CURRENT_TASK.with(|current| current.get()) {
+        Some(id) => id,
+        None => return,
+    };
+    let mut board = board();
+    let is_live = board.is_live;
+    let line = match board.tasks.iter_mut().find(|line| line.id == id) {
+        Some(line) => line,
+        None => return,
+    };
+    if is_live {
+        line.status = Some(status);
+        board.draw(false);
+    } else if line.printed_at.elapsed() >= PLAIN_INTERVAL {
+        line.printed_at = Instant::now();
+        println!("[{}] Progress {} {}", timestamp(), line.label, status);
+        let _ = io::stdout().flush();
+    }
+}
+
+// Fits a line of the live display into the width of the terminal, so it does not wrap
+pub fn fit_line(line: &str, columns: usize) -> String {
+    let width = columns.saturating_sub(1);
+    if line.chars().count() <= width {
+        return line.to_owned()
+    }
+    let mut fitted: String = line.chars().take(width.saturating_sub(3)).collect();
+    fitted.push_str("...");
+    fitted
+}
+
+thread_local! {
+    static CURRENT_TASK: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+struct Line {
+    id: usize,
+    label: String,
+    status: Option<String>,
+    printed_at: Instant,
+}
+
+struct Board {
+    is_live: bool,
+    columns: usize,
+    tasks: Vec<Line>,
+    summary: Option<(String, usize, usize)>,
+    next_id: usize,
+    drawn: usize,
+    drawn_at: Option<Instant>,
+}
+
+impl Board {
+    fn clear(&mut self) {
+        if self.drawn > 0 {
+            print!("\x1b[{}A\r\x1b[J", self.drawn);
+            let _ = io::stdout().flush();
+            self.drawn = 0;
+        }
+    }
+
+    // A forced draw follows a change of the tasks, an update of a status is throttled
+    fn draw(&mut self, is_forced: bool) {
+        if !self.is_live || (!is_forced && self.drawn_at.map_or(false, |drawn_at| drawn_at.elapsed() < REDRAW_INTERVAL)) {
+            return
+        }
+        let mut lines = Vec::new();
+        if let Some((ref title, done, total)) = self.summary {
+            if !self.tasks.is_empty() {
+                lines.push(format!("{} {}/{} package(s)", title, done, total));
+            }
+        }
+        let indent = if lines.is_empty() { "" } else { "  " };
+        for task in self.tasks.iter() {
+            if let Some(ref status) = task.status {
+                lines.push(format!("{}{} {}", indent, task.label, status));
+            }
+        }
+        self.clear();
+        for line in lines.iter() {
+            println!("{}", fit_line(line, self.columns));
+        }
+        let _ = io::stdout().flush();
+        self.drawn = lines.len();
+        self.drawn_at = Some(Instant::now());
+    }
+}
+
+lazy_static! {
+    static ref BOARD: Mutex<Board> = Mutex::new(Board {
+        is_live: atty::is(atty::Stream::Stdout) && env::var("TERM").map_or(true, |term| term != "dumb"),
+        columns: env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(DEFAULT_COLUMNS),
+        tasks: Vec::new(),
+        summary: None,
+        next_id: 0,
+        drawn: 0,
+        drawn_at: None,
+    });
+}
+
+fn board() -> MutexGuard<'static, Board> {
+    BOARD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Messages are printed above the live display, which is drawn again under them
+fn above_progress<F: FnOnce()>(print: F) {
+    let mut board = board();
+    if board.drawn == 0 {
+        return print()
+    }
+    board.clear();
+    print();
+    board.draw(true);
+}
+
+fn timestamp() -> String {
+    time::strftime("%T", &time::now()).unwrap_or(String::from("00:00:00"))
 }
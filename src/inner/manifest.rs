@@ -0,0 +1,732 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+use json::{self, JsonValue};
+use inner::{helpers, json_helper};
+use inner::vcs;
+
+const INFO_FIELDS: &[&str] = &[json_helper::NAME_KEY, json_helper::IMPORT_KEY, json_helper::DESCRIPTION_KEY, json_helper::HOMEPAGE_KEY, json_helper::LICENSE_KEY, json_helper::AUTHORS_KEY];
+const AUTHOR_FIELDS: &[&str] = &[json_helper::NAME_KEY, json_helper::EMAIL_KEY, json_helper::WEBSITE_KEY];
+const MANIFEST_FIELDS: &[&str] = &[json_helper::INFO_KEY, json_helper::PACKAGES_KEY];
+const PACKAGES_FIELDS: &[&str] = &[json_helper::GIT_KEY, json_helper::LOCAL_KEY, json_helper::GLOBAL_KEY];
+const GIT_FIELDS: &[&str] = &[json_helper::IMPORT_KEY, json_helper::REPO_KEY, json_helper::VCS_KEY, json_helper::VERSION_KEY, json_helper::EXCLUDE_KEY];
+const LOCKED_GIT_FIELDS: &[&str] = &[json_helper::IMPORT_KEY, json_helper::REPO_KEY, json_helper::URL_KEY, json_helper::VCS_KEY, json_helper::VERSION_KEY, json_helper::EXCLUDE_KEY, json_helper::COMMIT_KEY, json_helper::HASH_KEY, json_helper::SUM_KEY, json_helper::PARENT_KEY];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Invalid(Vec<Violation>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "{}", e),
+            LoadError::Invalid(ref violations) => {
+                write!(f, "found {} problem(s)", violations.len())?;
+                for violation in violations {
+                    write!(f, "\n  {}", violation)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl From<LoadError> for io::Error {
+    fn from(err: LoadError) -> io::Error {
+        match err {
+            LoadError::Io(e) => e,
+            invalid => io::Error::new(io::ErrorKind::InvalidData, format!("{}", invalid)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Author {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    pub name: Option<String>,
+    pub import: Option<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    pub authors: Vec<Author>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitPackage {
+    pub import: String,
+    pub repo: Option<String>,
+    pub vcs: Option<String>,
+    pub version: String,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub import: String,
+    pub repo: Option<String>,
+    pub url: Option<String>,
+    pub vcs: Option<String>,
+    pub version: String,
+    pub exclude: Vec<String>,
+    pub commit: Option<String>,
+    pub hash: Option<String>,
+    pub sum: Option<String>,
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Packages {
+    pub git: Vec<GitPackage>,
+    pub local: Vec<String>,
+    pub global: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub info: Info,
+    pub packages: Packages,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lock {
+    pub git: Vec<LockedPackage>,
+    pub local: Vec<String>,
+    pub global: Vec<String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest, LoadError> {
+        let contents = read_file(path)?;
+        Manifest::parse(&file_name(path), contents.as_str()).map_err(LoadError::Invalid)
+    }
+
+    pub fn parse(file: &str, contents: &str) -> Result<Manifest, Vec<Violation>> {
+        let (value, mut checker) = Checker::new(file, contents)?;
+        let manifest = checker.manifest(&value);
+        checker.finish(manifest)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut info = object!{};
+        set_optional(&mut info, json_helper::NAME_KEY, &self.info.name);
+        set_optional(&mut info, json_helper::IMPORT_KEY, &self.info.import);
+        set_optional(&mut info, json_helper::DESCRIPTION_KEY, &self.info.description);
+        set_optional(&mut info, json_helper::HOMEPAGE_KEY, &self.info.homepage);
+        set_optional(&mut info, json_helper::LICENSE_KEY, &self.info.license);
+        if !self.info.authors.is_empty() {
+            let mut authors = array![];
+            for author in &self.info.authors {
+                let mut author_json = object!{};
+                set_optional(&mut author_json, json_helper::NAME_KEY, &author.name);
+                set_optional(&mut author_json, json_helper::EMAIL_KEY, &author.email);
+                set_optional(&mut author_json, json_helper::WEBSITE_KEY, &author.website);
+                let _ = authors.push(author_json);
+            }
+            info[json_helper::AUTHORS_KEY] = authors;
+        }
+
+        object!{
+            json_helper::INFO_KEY => info,
+            json_helper::PACKAGES_KEY => self.packages.to_json()
+        }
+    }
+}
+
+impl Packages {
+    pub fn to_json(&self) -> JsonValue {
+        object!{
+            json_helper::GIT_KEY => self.git_to_json(),
+            json_helper::LOCAL_KEY => strings_to_json(&self.local),
+            json_helper::GLOBAL_KEY => strings_to_json(&self.global)
+        }
+    }
+
+    pub fn git_to_json(&self) -> JsonValue {
+        let mut git = array![];
+        for pkg in &self.git {
+            let _ = git.push(pkg.to_json());
+        }
+        git
+    }
+}
+
+impl Lock {
+    pub fn load(path: &Path) -> Result<Lock, LoadError> {
+        let contents = read_file(path)?;
+        Lock::parse(&file_name(path), contents.as_str()).map_err(LoadError::Invalid)
+    }
+
+    pub fn parse(file: &str, contents: &str) -> Result<Lock, Vec<Violation>> {
+        let (value, mut checker) = Checker::new(file, contents)?;
+        let lock = checker.lock(&value);
+        checker.finish(lock)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object!{
+            json_helper::GIT_KEY => self.git_to_json(),
+            json_helper::LOCAL_KEY => strings_to_json(&self.local),
+            json_helper::GLOBAL_KEY => strings_to_json(&self.global)
+        }
+    }
+
+    pub fn git_to_json(&self) -> JsonValue {
+        let mut git = array![];
+        for pkg in &self.git {
+            let _ = git.push(pkg.to_json());
+        }
+        git
+    }
+
+    // Replaces every entry of the package, regardless of the url scheme of its import, and appends it
+    pub fn replace_package(&mut self, pkg: LockedPackage) {
+        let pkg_import = helpers::strip_url_scheme(pkg.import.as_str());
+        self.git.retain(|locked_pkg| helpers::strip_url_scheme(locked_pkg.import.as_str()) != pkg_import);
+        self.git.push(pkg);
+    }
+}
+
+impl GitPackage {
+    pub fn from_json(pkg: &JsonValue) -> Option<GitPackage> {
+        Some(GitPackage {
+            import: pkg[json_helper::IMPORT_KEY].as_str()?.to_owned(),
+            repo: pkg[json_helper::REPO_KEY].as_str().map(|s| s.to_owned()),
+            vcs: pkg[json_helper::VCS_KEY].as_str().map(|s| s.to_owned()),
+            version: pkg[json_helper::VERSION_KEY].as_str()?.to_owned(),
+            exclude: json_to_strings(&pkg[json_helper::EXCLUDE_KEY]),
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut pkg = object!{
+            json_helper::IMPORT_KEY => self.import.as_str()
+        };
+        set_optional(&mut pkg, json_helper::REPO_KEY, &self.repo);
+        set_optional(&mut pkg, json_helper::VCS_KEY, &self.vcs);
+        pkg[json_helper::VERSION_KEY] = self.version.as_str().into();
+        if !self.exclude.is_empty() {
+            pkg[json_helper::EXCLUDE_KEY] = strings_to_json(&self.exclude);
+        }
+        pkg
+    }
+}
+
+impl LockedPackage {
+    // The package at its locked commit, so the dependencies of exactly this revision are resolved
+    pub fn pinned(&self) -> GitPackage {
+        GitPackage {
+            import: self.import.clone(),
+            repo: self.repo.clone(),
+            vcs: self.vcs.clone(),
+            version: self.commit.clone().unwrap_or_else(|| self.version.clone()),
+            exclude: self.exclude.clone(),
+        }
+    }
+
+    pub fn from_json(pkg: &JsonValue) -> Option<LockedPackage> {
+        Some(LockedPackage {
+            import: pkg[json_helper::IMPORT_KEY].as_str()?.to_owned(),
+            repo: pkg[json_helper::REPO_KEY].as_str().map(|s| s.to_owned()),
+            url: pkg[json_helper::URL_KEY].as_str().map(|s| s.to_owned()),
+            vcs: pkg[json_helper::VCS_KEY].as_str().map(|s| s.to_owned()),
+            version: pkg[json_helper::VERSION_KEY].as_str()?.to_owned(),
+            exclude: json_to_strings(&pkg[json_helper::EXCLUDE_KEY]),
+            commit: pkg[json_helper::COMMIT_KEY].as_str().map(|s| s.to_owned()),
+            hash: pkg[json_helper::HASH_KEY].as_str().map(|s| s.to_owned()),
+            sum: pkg[json_helper::SUM_KEY].as_str().map(|s| s.to_owned()),
+            parent: pkg[json_helper::PARENT_KEY].as_str().map(|s| s.to_owned()),
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut pkg = object!{
+            json_helper::IMPORT_KEY => self.import.as_str()
+        };
+        set_optional(&mut pkg, json_helper::REPO_KEY, &self.repo);
+        set_optional(&mut pkg, json_helper::URL_KEY, &self.url);
+        set_optional(&mut pkg, json_helper::VCS_KEY, &self.vcs);
+        pkg[json_helper::VERSION_KEY] = self.version.as_str().into();
+        if !self.exclude.is_empty() {
+            pkg[json_helper::EXCLUDE_KEY] = strings_to_json(&self.exclude);
+        }
+        set_optional(&mut pkg, json_helper::COMMIT_KEY, &self.commit);
+        set_optional(&mut pkg, json_helper::HASH_KEY, &self.hash);
+        set_optional(&mut pkg, json_helper::SUM_KEY, &self.sum);
+        set_optional(&mut pkg, json_helper::PARENT_KEY, &self.parent);
+        pkg
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, LoadError> {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(mut file) => match file.read_to_string(&mut contents) {
+            Ok(_) => Ok(contents),
+            Err(e) => Err(LoadError::Io(e)),
+        },
+        Err(e) => Err(LoadError::Io(e)),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.to_str().unwrap_or("unknown").to_owned()
+}
+
+fn set_optional(object: &mut JsonValue, key: &str, value: &Option<String>) {
+    if let Some(ref val) = *value {
+        object[key] = val.as_str().into();
+    }
+}
+
+pub fn strings_to_json(values: &[String]) -> JsonValue {
+    let mut array = array![];
+    for val in values {
+        let _ = array.push(val.as_str());
+    }
+    array
+}
+
+pub fn json_to_strings(values: &JsonValue) -> Vec<String> {
+    values.members().filter_map(|val| val.as_str().map(|s| s.to_owned())).collect()
+}
+
+pub fn locked_packages(packages: &JsonValue) -> Vec<LockedPackage> {
+    packages.members().filter_map(LockedPackage::from_json).collect()
+}
+
+fn kind(value: &JsonValue) -> &'static str {
+    match *value {
+        JsonValue::Null => "null",
+        JsonValue::Short(_) | JsonValue::String(_) => "a string",
+        JsonValue::Number(_) => "a number",
+        JsonValue::Boolean(_) => "a boolean",
+        JsonValue::Object(_) => "an object",
+        JsonValue::Array(_) => "an array",
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn index_path(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Default)]
+struct Locations {
+    keys: HashMap<String, Position>,
+    values: HashMap<String, Position>,
+}
+
+// Walks the raw text of an already parsed document and records where every
+// key and value starts, so violations can point to the offending token.
+struct Locator<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+    locations: Locations,
+}
+
+impl<'a> Locator<'a> {
+    fn locate(contents: &'a str) -> Locations {
+        let mut locator = Locator {
+            chars: contents.chars().peekable(),
+            position: Position { line: 1, column: 1 },
+            locations: Locations::default(),
+        };
+        locator.value(String::new());
+        locator.locations
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next();
+        match ch {
+            Some('\n') => {
+                self.position.line += 1;
+                self.position.column = 1;
+            },
+            Some(_) => self.position.column += 1,
+            None => (),
+        }
+        ch
+    }
+
+    fn skip_whitespace(&mut self) {
+        while match self.chars.peek() {
+            Some(ch) => ch.is_whitespace(),
+            None => false,
+        } {
+            self.bump();
+        }
+    }
+
+    fn value(&mut self, path: String) {
+        self.skip_whitespace();
+        self.locations.values.insert(path.clone(), self.position);
+        match self.chars.peek().cloned() {
+            Some('{') => self.object(path),
+            Some('[') => self.array(path),
+            Some('"') => {
+                self.string();
+            },
+            Some(_) => while match self.chars.peek() {
+                Some(&ch) => !(ch == ',' || ch == '}' || ch == ']' || ch.is_whitespace()),
+                None => false,
+            } {
+                self.bump();
+            },
+            None => (),
+        }
+    }
+
+    fn object(&mut self, path: String) {
+        self.bump();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek().cloned() {
+                Some('"') => (),
+                Some('}') => {
+                    self.bump();
+                    return
+                },
+                _ => return,
+            }
+            let key_position = self.position;
+            let key_path = child_path(path.as_str(), self.string().as_str());
+            self.locations.keys.insert(key_path.clone(), key_position);
+            self.skip_whitespace();
+            if self.bump() != Some(':') {
+                return
+            }
+            self.value(key_path);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => continue,
+                _ => return,
+            }
+        }
+    }
+
+    fn array(&mut self, path: String) {
+        self.bump();
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() == Some(&']') {
+                self.bump();
+                return
+            }
+            self.value(index_path(path.as_str(), index));
+            index += 1;
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => continue,
+                _ => return,
+            }
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let mut result = String::new();
+        self.bump();
+        while let Some(ch) = self.bump() {
+            match ch {
+                '"' => break,
+                '\\' => if let Some(escaped) = self.bump() {
+                    result.push(escaped);
+                },
+                _ => result.push(ch),
+            }
+        }
+        result
+    }
+}
+
+struct Checker {
+    file: String,
+    locations: Locations,
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn new(file: &str, contents: &str) -> Result<(JsonValue, Checker), Vec<Violation>> {
+        match json::parse(contents) {
+            Ok(value) => Ok((value, Checker {
+                file: file.to_owned(),
+                locations: Locator::locate(contents),
+                violations: Vec::new(),
+            })),
+            Err(e) => {
+                let (line, column) = match e {
+                    json::Error::UnexpectedCharacter { line, column, .. } => (line, column),
+                    _ => {
+                        let line = contents.lines().count().max(1);
+                        (line, contents.lines().last().map(|l| l.chars().count() + 1).unwrap_or(1))
+                    },
+                };
+                Err(vec![Violation {
+                    file: file.to_owned(),
+                    line,
+                    column,
+                    message: format!("invalid json: {}", e),
+                }])
+            },
+        }
+    }
+
+    fn finish<T>(self, result: T) -> Result<T, Vec<Violation>> {
+        if self.violations.is_empty() {
+            Ok(result)
+        } else {
+            Err(self.violations)
+        }
+    }
+
+    fn report(&mut self, position: Option<Position>, message: String) {
+        let position = position.unwrap_or(Position { line: 1, column: 1 });
+        self.violations.push(Violation {
+            file: self.file.clone(),
+            line: position.line,
+            column: position.column,
+            message,
+        });
+    }
+
+    fn report_value(&mut self, path: &str, message: String) {
+        let position = self.locations.values.get(path).cloned();
+        self.report(position, message);
+    }
+
+    fn display(path: &str) -> String {
+        if path.is_empty() {
+            String::from("<root>")
+        } else {
+            path.to_owned()
+        }
+    }
+
+    fn object(&mut self, value: &JsonValue, path: &str, fields: &[&str]) -> bool {
+        if !value.is_object() {
+            let message = format!("`{}`: expected an object, found {}", Checker::display(path), kind(value));
+            self.report_value(path, message);
+            return false
+        }
+        for (key, _) in value.entries() {
+            if fields.contains(&key) {
+                continue
+            }
+            let position = self.locations.keys.get(&child_path(path, key)).cloned();
+            let expected = fields.iter().map(|f| format!("`{}`", f)).collect::<Vec<_>>().join(", ");
+            self.report(position, format!("`{}`: unknown field `{}`, expected one of {}", Checker::display(path), key, expected));
+        }
+        true
+    }
+
+    fn string(&mut self, value: &JsonValue, path: &str, required: bool) -> Option<String> {
+        match value.as_str() {
+            Some(val) => Some(val.to_owned()),
+            None if value.is_null() => {
+                if required {
+                    let (parent, field) = match path.rfind('.') {
+                        Some(index) => (&path[..index], &path[index + 1..]),
+                        None => ("", path),
+                    };
+                    let message = format!("`{}`: missing required field `{}`", Checker::display(parent), field);
+                    self.report_value(parent, message);
+                }
+                None
+            },
+            None => {
+                self.report_value(path, format!("`{}`: expected a string, found {}", path, kind(value)));
+                None
+            },
+        }
+    }
+
+    fn array<'v>(&mut self, value: &'v JsonValue, path: &str) -> Vec<(String, &'v JsonValue)> {
+        if value.is_null() {
+            return Vec::new()
+        }
+        if !value.is_array() {
+            self.report_value(path, format!("`{}`: expected an array, found {}", path, kind(value)));
+            return Vec::new()
+        }
+        value.members().enumerate().map(|(i, member)| (index_path(path, i), member)).collect()
+    }
+
+    fn string_array(&mut self, value: &JsonValue, path: &str, item: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        for (member_path, member) in self.array(value, path) {
+            match member.as_str() {
+                Some(val) => {
+                    if result.iter().any(|r: &String| r == val) {
+                        self.report_value(member_path.as_str(), format!("`{}`: duplicate {} `{}`", member_path, item, val));
+                    }
+                    result.push(val.to_owned());
+                },
+                None => self.report_value(member_path.as_str(), format!("`{}`: expected a string, found {}", member_path, kind(member))),
+            }
+        }
+        result
+    }
+
+    fn vcs(&mut self, value: &JsonValue, path: &str) -> Option<String> {
+        let name = self.string(value, path, false)?;
+        if vcs::Kind::from_name(name.as_str()).is_none() {
+            let expected = vcs::KINDS.iter().map(|kind| format!("`{}`", kind.name())).collect::<Vec<_>>().join(", ");
+            self.report_value(path, format!("`{}`: unknown vcs `{}`, expected one of {}", path, name, expected));
+        }
+        Some(name)
+    }
+
+    fn manifest(&mut self, value: &JsonValue) -> Manifest {
+        let mut manifest = Manifest::default();
+        if !self.object(value, "", MANIFEST_FIELDS) {
+            return manifest
+        }
+
+        let info = &value[json_helper::INFO_KEY];
+        if !info.is_null() && self.object(info, json_helper::INFO_KEY, INFO_FIELDS) {
+            manifest.info = self.info(info);
+        }
+
+        let packages = &value[json_helper::PACKAGES_KEY];
+        if !packages.is_null() && self.object(packages, json_helper::PACKAGES_KEY, PACKAGES_FIELDS) {
+            let git_path = child_path(json_helper::PACKAGES_KEY, json_helper::GIT_KEY);
+            for (pkg_path, pkg) in self.array(&packages[json_helper::GIT_KEY], git_path.as_str()) {
+                if !self.object(pkg, pkg_path.as_str(), GIT_FIELDS) {
+                    continue
+                }
+                let import = self.string(&pkg[json_helper::IMPORT_KEY], child_path(pkg_path.as_str(), json_helper::IMPORT_KEY).as_str(), true);
+                let repo = self.string(&pkg[json_helper::REPO_KEY], child_path(pkg_path.as_str(), json_helper::REPO_KEY).as_str(), false);
+                let vcs = self.vcs(&pkg[json_helper::VCS_KEY], child_path(pkg_path.as_str(), json_helper::VCS_KEY).as_str());
+                let version = self.string(&pkg[json_helper::VERSION_KEY], child_path(pkg_path.as_str(), json_helper::VERSION_KEY).as_str(), true);
+                let exclude = self.string_array(&pkg[json_helper::EXCLUDE_KEY], child_path(pkg_path.as_str(), json_helper::EXCLUDE_KEY).as_str(), "version");
+                if let (Some(import), Some(version)) = (import, version) {
+                    if manifest.packages.git.iter().any(|p| p.import == import) {
+                        self.report_value(pkg_path.as_str(), format!("`{}`: duplicate package `{}`", pkg_path, import));
+                    }
+                    manifest.packages.git.push(GitPackage {
+                        import,
+                        repo,
+                        vcs,
+                        version,
+                        exclude,
+                    });
+                }
+            }
+            manifest.packages.local = self.string_array(&packages[json_helper::LOCAL_KEY], child_path(json_helper::PACKAGES_KEY, json_helper::LOCAL_KEY).as_str(), "package");
+            manifest.packages.global = self.string_array(&packages[json_helper::GLOBAL_KEY], child_path(json_helper::PACKAGES_KEY, json_helper::GLOBAL_KEY).as_str(), "package");
+        }
+        manifest
+    }
+
+    fn info(&mut self, value: &JsonValue) -> Info {
+        let path = json_helper::INFO_KEY;
+        let mut info = Info {
+            name: self.string(&value[json_helper::NAME_KEY], child_path(path, json_helper::NAME_KEY).as_str(), false),
+            import: self.string(&value[json_helper::IMPORT_KEY], child_path(path, json_helper::IMPORT_KEY).as_str(), false),
+            description: self.string(&value[json_helper::DESCRIPTION_KEY], child_path(path, json_helper::DESCRIPTION_KEY).as_str(), false),
+            homepage: self.string(&value[json_helper::HOMEPAGE_KEY], child_path(path, json_helper::HOMEPAGE_KEY).as_str(), false),
+            license: self.string(&value[json_helper::LICENSE_KEY], child_path(path, json_helper::LICENSE_KEY).as_str(), false),
+            authors: Vec::new(),
+        };
+        for (author_path, author) in self.array(&value[json_helper::AUTHORS_KEY], child_path(path, json_helper::AUTHORS_KEY).as_str()) {
+            if !self.object(author, author_path.as_str(), AUTHOR_FIELDS) {
+                continue
+            }
+            info.authors.push(Author {
+                name: self.string(&author[json_helper::NAME_KEY], child_path(author_path.as_str(), json_helper::NAME_KEY).as_str(), false),
+                email: self.string(&author[json_helper::EMAIL_KEY], child_path(author_path.as_str(), json_helper::EMAIL_KEY).as_str(), false),
+                website: self.string(&author[json_helper::WEBSITE_KEY], child_path(author_path.as_str(), json_helper::WEBSITE_KEY).as_str(), false),
+            });
+        }
+        info
+    }
+
+    fn lock(&mut self, value: &JsonValue) -> Lock {
+        let mut lock = Lock::default();
+        if !self.object(value, "", PACKAGES_FIELDS) {
+            return lock
+        }
+
+        for (pkg_path, pkg) in self.array(&value[json_helper::GIT_KEY], json_helper::GIT_KEY) {
+            if !self.object(pkg, pkg_path.as_str(), LOCKED_GIT_FIELDS) {
+                continue
+            }
+            let import = self.string(&pkg[json_helper::IMPORT_KEY], child_path(pkg_path.as_str(), json_helper::IMPORT_KEY).as_str(), true);
+            let repo = self.string(&pkg[json_helper::REPO_KEY], child_path(pkg_path.as_str(), json_helper::REPO_KEY).as_str(), false);
+            let url = self.string(&pkg[json_helper::URL_KEY], child_path(pkg_path.as_str(), json_helper::URL_KEY).as_str(), false);
+            let vcs = self.vcs(&pkg[json_helper::VCS_KEY], child_path(pkg_path.as_str(), json_helper::VCS_KEY).as_str());
+            let version = self.string(&pkg[json_helper::VERSION_KEY], child_path(pkg_path.as_str(), json_helper::VERSION_KEY).as_str(), true);
+            let exclude = self.string_array(&pkg[json_helper::EXCLUDE_KEY], child_path(pkg_path.as_str(), json_helper::EXCLUDE_KEY).as_str(), "version");
+            let commit_path = child_path(pkg_path.as_str(), json_helper::COMMIT_KEY);
+            let commit = self.string(&pkg[json_helper::COMMIT_KEY], commit_path.as_str(), false);
+            if let Some(ref sha) = commit {
+                // The commit of a module is its version
+                if vcs.as_deref() != Some(vcs::Kind::Mod.name()) && (sha.len() != 40 || !sha.chars().all(|c| c.is_ascii_hexdigit())) {
+                    self.report_value(commit_path.as_str(), format!("`{}`: expected a full commit SHA, found `{}`", commit_path, sha));
+                }
+            }
+            let hash = self.string(&pkg[json_helper::HASH_KEY], child_path(pkg_path.as_str(), json_helper::HASH_KEY).as_str(), false);
+            let sum = self.string(&pkg[json_helper::SUM_KEY], child_path(pkg_path.as_str(), json_helper::SUM_KEY).as_str(), false);
+            let parent = self.string(&pkg[json_helper::PARENT_KEY], child_path(pkg_path.as_str(), json_helper::PARENT_KEY).as_str(), false);
+            if let (Some(import), Some(version)) = (import, version) {
+                if lock.git.iter().any(|p| p.import == import) {
+                    self.report_value(pkg_path.as_str(), format!("`{}`: duplicate package `{}`", pkg_path, import));
+                }
+                lock.git.push(LockedPackage {
+                    import,
+                    repo,
+                    url,
+                    vcs,
+                    version,
+                    exclude,
+                    commit,
+                    hash,
+                    sum,
+                    parent,
+                });
+            }
+        }
+        lock.local = self.string_array(&value[json_helper::LOCAL_KEY], json_helper::LOCAL_KEY, "package");
+        lock.global = self.string_array(&value[json_helper::GLOBAL_KEY], json_helper::GLOBAL_KEY, "package");
+        lock
+    }
+}
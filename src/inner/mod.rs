@@ -1,6 +1,25 @@
 pub mod logger;
 pub mod vendor;
 pub mod json_helper;
+pub mod manifest;
+pub mod integrity;
+pub mod importer;
+pub mod gomod;
+pub mod resolver;
+pub mod vcs;
+pub mod cache;
+pub mod config;
+pub mod context;
+pub mod auth;
+pub mod goget;
+pub mod http;
+pub mod network;
+pub mod jobs;
+pub mod project_lock;
+pub mod staging;
+pub mod modproxy;
+pub mod imports;
+pub mod graph;
 pub mod git_helper;
 pub mod helpers;
 pub mod go;
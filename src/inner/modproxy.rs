@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use json::{self, JsonValue};
+use zip::ZipArchive;
+use inner::{cache, gomod, http, resolver};
+use inner::context::Context;
+
+pub const VCS_NAME: &str = "mod";
+pub const META_DIR: &str = ".rubigo-mod";
+pub const DEFAULT_PROXY: &str = "https://proxy.golang.org";
+const META_FILE: &str = "module.json";
+
+// The `proxy` entry of the configuration wins over the first proxy of `GOPROXY`
+pub fn proxy_url(config: &JsonValue) -> String {
+    if let Some(proxy) = config["proxy"].as_str().filter(|proxy| !proxy.is_empty()) {
+        return proxy.trim_end_matches('/').to_owned()
+    }
+    let goproxy = env::var("GOPROXY").unwrap_or_default();
+    match goproxy.split(&[',', '|'][..]).map(|proxy| proxy.trim()).find(|proxy| !proxy.is_empty() && *proxy != "direct" && *proxy != "off") {
+        Some(proxy) => proxy.trim_end_matches('/').to_owned(),
+        None => DEFAULT_PROXY.to_owned(),
+    }
+}
+
+// Uppercase letters are escaped as `!` followed by the lowercase letter, so paths also work on case-insensitive file systems
+pub fn escape_path(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(c.to_ascii_lowercase());
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+pub fn module_url(proxy: &str, module_path: &str) -> String {
+    format!("{}/{}", proxy.trim_end_matches('/'), escape_path(module_path))
+}
+
+// Files of the module zip are prefixed by `<module>@<version>/`
+fn strip_zip_prefix(name: &str) -> Option<&str> {
+    let at = name.find('@')?;
+    let slash = name[at..].find('/')?;
+    Some(&name[at + slash + 1..])
+}
+
+pub fn hash_zip(contents: &[u8]) -> Result<String, String> {
+    let mut archive = ZipArchive::new(Cursor::new(contents)).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+        if file.is_dir() {
+            continue
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+        files.push((file.name().to_owned(), data));
+    }
+    Ok(gomod::hash_files(files))
+}
+
+// A module vendored from a proxy keeps its source url, version and hash in `.rubigo-mod/module.json`
+pub struct Module {
+    dir_path: PathBuf,
+    ctx: Context,
+    url: RefCell<String>,
+    meta: RefCell<JsonValue>,
+    versions: RefCell<Option<Vec<String>>>,
+}
+
+impl Module {
+    pub fn create(url: &str, dir_path: &Path, ctx: &Context) -> Result<Module, String> {
+        let module = Module {
+            dir_path: dir_path.to_path_buf(),
+            ctx: ctx.clone(),
+            url: RefCell::new(url.to_owned()),
+            meta: RefCell::new(object!{
+                "url" => url
+            }),
+            versions: RefCell::new(None),
+        };
+        module.write_meta()?;
+        Ok(module)
+    }
+
+    pub fn open(dir_path: &Path, ctx: &Context) -> Result<Module, String> {
+        let meta_path = dir_path.join(META_DIR).join(META_FILE);
+        let mut contents = String::new();
+        File::open(meta_path.as_path()).and_then(|mut file| file.read_to_string(&mut contents)).map_err(|_| format!("`{}` is not a module", dir_path.to_string_lossy()))?;
+        let meta = json::parse(contents.as_str()).map_err(|e| format!("unable to parse `{}`: {}", meta_path.to_string_lossy(), e))?;
+        let url = match meta["url"].as_str() {
+            Some(url) => url.to_owned(),
+            None => return Err(format!("`{}` has no `url`", meta_path.to_string_lossy())),
+        };
+        Ok(Module {
+            dir_path: dir_path.to_path_buf(),
+            ctx: ctx.clone(),
+            url: RefCell::new(url),
+            meta: RefCell::new(meta),
+            versions: RefCell::new(None),
+        })
+    }
+
+    fn write_meta(&self) -> Result<(), String> {
+        let meta_dir = self.dir_path.join(META_DIR);
+        fs::create_dir_all(meta_dir.as_path())
+            .and_then(|_| File::create(meta_dir.join(META_FILE)))
+            .and_then(|mut file| file.write_all(self.meta.borrow().pretty(2).as_bytes()))
+            .map_err(|e| e.to_string())
+    }
+
+    // Immutable files are served from the cache, the version list is only read from it offline or if the proxy fails
+    fn get(&self, file_path: &str, is_immutable: bool) -> Result<Vec<u8>, String> {
+        let url = self.url.borrow().clone();
+        let mirror_path = cache::mirror_path(self.ctx.cache_dir.as_deref(), VCS_NAME, url.as_str());
+        let cached = || -> Option<Vec<u8>> {
+            let mut contents = Vec::new();
+            File::open(mirror_path.as_ref()?.join(file_path)).and_then(|mut file| file.read_to_end(&mut contents)).ok()?;
+            Some(contents)
+        };
+        if is_immutable || self.ctx.offline {
+            if let Some(contents) = cached() {
+                return Ok(contents)
+            }
+            if self.ctx.offline {
+                return Err(format!("`{}/{}` is not in the cache", url, file_path))
+            }
+        }
+        let contents = match http::get(format!("{}/{}", url, file_path).as_str(), &self.ctx.config) {
+            Ok(contents) => contents,
+            Err(e) => return cached().ok_or(e),
+        };
+        if let Some(mirror_path) = mirror_path {
+            let file_path_buf = mirror_path.join(file_path);
+            let _ = file_path_buf.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| File::create(file_path_buf.as_path()))
+                .and_then(|mut file| file.write_all(&contents))
+                .and_then(|_| cache::touch(mirror_path.as_path()));
+        }
+        Ok(contents)
+    }
+
+    pub fn refresh(&self) {
+        *self.versions.borrow_mut() = None;
+    }
+
+    pub fn versions(&self) -> Result<Vec<String>, String> {
+        if let Some(ref versions) = *self.versions.borrow() {
+            return Ok(versions.clone())
+        }
+        let list = self.get("@v/list", false)?;
+        let versions: Vec<String> = String::from_utf8_lossy(&list).lines().map(|line| line.trim()).filter(|line| !line.is_empty()).map(|line| line.to_owned()).collect();
+        *self.versions.borrow_mut() = Some(versions.clone());
+        Ok(versions)
+    }
+
+    // Returns the canonical version of a version, or of a query the proxy understands such as a branch
+    pub fn resolve(&self, query: &str) -> Option<String> {
+        if self.versions().map(|versions| versions.iter().any(|version| version == query)).unwrap_or(false) {
+            return Some(query.to_owned())
+        }
+        let info = self.get(format!("@v/{}.info", escape_path(query)).as_str(), false).ok()?;
+        json::parse(String::from_utf8_lossy(&info).as_ref()).ok()?["Version"].as_str().map(|version| version.to_owned())
+    }
+
+    pub fn latest(&self) -> Option<String> {
+        if let Some(info) = self.get("@latest", false).ok().and_then(|info| json::parse(String::from_utf8_lossy(&info).as_ref()).ok()) {
+            if let Some(version) = info["Version"].as_str() {
+                return Some(version.to_owned())
+            }
+        }
+        let versions = self.versions().ok()?;
+        versions.iter().filter_map(|version| Some((version, resolver::tag_version(version)?))).max_by(|a, b| a.1.cmp(&b.1)).map(|(version, _)| version.to_owned())
+    }
+
+    fn zip(&self, version: &str) -> Result<Vec<u8>, String> {
+        self.get(format!("@v/{}.zip", escape_path(version)).as_str(), true)
+    }
+
+    // Replaces the files of the module with those of `version`, except its metadata
+    pub fn unpack(&self, version: &str) -> Result<(), String> {
+        let contents = self.zip(version)?;
+        let sum = hash_zip(&contents)?;
+        let mut archive = ZipArchive::new(Cursor::new(contents)).map_err(|e| e.to_string())?;
+        for entry in fs::read_dir(self.dir_path.as_path()).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if entry.file_name() == META_DIR {
+                continue
+            }
+            let result = if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+                fs::remove_dir_all(entry.path())
+            } else {
+                fs::remove_file(entry.path())
+            };
+            result.map_err(|e| e.to_string())?;
+        }
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+            let rel_path = match file.enclosed_name().and_then(|name| strip_zip_prefix(name.to_string_lossy().as_ref()).map(PathBuf::from)) {
+                Some(ref rel_path) if file.is_dir() || rel_path.as_os_str().is_empty() => continue,
+                Some(rel_path) => rel_path,
+                None => return Err(format!("invalid file `{}` in the zip of `{}`", file.name(), version)),
+            };
+            let file_path = self.dir_path.join(rel_path);
+            file_path.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| File::create(file_path.as_path()))
+                .and_then(|mut target| io::copy(&mut file, &mut target))
+                .map_err(|e| e.to_string())?;
+        }
+        {
+            let mut meta = self.meta.borrow_mut();
+            meta["version"] = version.into();
+            meta["sum"] = sum.into();
+        }
+        self.write_meta()
+    }
+
+    pub fn sum(&self) -> Option<String> {
+        self.meta.borrow()["sum"].as_str().map(|sum| sum.to_owned())
+    }
+
+    pub fn read_file(&self, version: &str, file_path: &str) -> Option<Vec<u8>> {
+        if file_path == "go.mod" {
+            return self.get(format!("@v/{}.mod", escape_path(version)).as_str(), true).ok()
+        }
+        let contents = self.zip(version).ok()?;
+        let mut archive = ZipArchive::new(Cursor::new(contents)).ok()?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).ok()?;
+            if strip_zip_prefix(file.name()) == Some(file_path) {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).ok()?;
+                return Some(data)
+            }
+        }
+        None
+    }
+
+    pub fn set_url(&self, url: &str) -> Result<(), String> {
+        if *self.url.borrow() == url {
+            return Ok(())
+        }
+        *self.url.borrow_mut() = url.to_owned();
+        self.meta.borrow_mut()["url"] = url.into();
+        self.refresh();
+        self.write_meta()
+    }
+}
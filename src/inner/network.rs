@@ -0,0 +1,127 @@
+use std::thread;
+use std::time::Duration;
+use json::JsonValue;
+
+pub const NETWORK_KEY: &str = "network";
+pub const DEFAULT_CONNECT_TIMEOUT: u64 = 30;
+pub const DEFAULT_TIMEOUT: u64 = 120;
+pub const DEFAULT_RETRIES: u64 = 3;
+pub const DEFAULT_BACKOFF: u64 = 1000;
+const MAX_BACKOFF: u64 = 30_000;
+const TRANSIENT_MESSAGES: &[&str] = &["timed out", "connection reset", "connection refused", "connection aborted", "broken pipe", "temporary failure", "network is unreachable", "http error 429", "http error 408"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            retries: DEFAULT_RETRIES as u32,
+            backoff: Duration::from_millis(DEFAULT_BACKOFF),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub message: String,
+    pub is_transient: bool,
+}
+
+impl Failure {
+    pub fn transient<T: Into<String>>(message: T) -> Self {
+        Failure {
+            message: message.into(),
+            is_transient: true,
+        }
+    }
+
+    pub fn permanent<T: Into<String>>(message: T) -> Self {
+        Failure {
+            message: message.into(),
+            is_transient: false,
+        }
+    }
+}
+
+// The timeouts are in seconds and the backoff in milliseconds, a timeout of `0` disables it
+pub fn settings(config: &JsonValue) -> Result<Settings, String> {
+    let network = &config[NETWORK_KEY];
+    let number = |key: &str, default: u64| match network[key] {
+        JsonValue::Null => Ok(default),
+        ref value => value.as_u64().ok_or_else(|| format!("`{}.{}` should be a non-negative integer, found `{}`", NETWORK_KEY, key, value)),
+    };
+    Ok(Settings {
+        connect_timeout: Duration::from_secs(number("connect_timeout", DEFAULT_CONNECT_TIMEOUT)?),
+        timeout: Duration::from_secs(number("timeout", DEFAULT_TIMEOUT)?),
+        retries: number("retries", DEFAULT_RETRIES)? as u32,
+        backoff: Duration::from_millis(number("backoff", DEFAULT_BACKOFF)?),
+    })
+}
+
+pub fn backoff_delay(settings: &Settings, retry: u32) -> Duration {
+    let delay = (settings.backoff.as_millis() as u64).saturating_mul(1 << retry.saturating_sub(1).min(16));
+    Duration::from_millis(delay.min(MAX_BACKOFF))
+}
+
+pub fn is_transient_status(status: u32) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+// Recognizes the network errors in the messages of tools such as `hg`
+pub fn is_transient_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    TRANSIENT_MESSAGES.iter().any(|pattern| message.contains(pattern)) || message.contains("http error 5")
+}
+
+// Runs `operation` again after a growing delay as long as it fails with a transient error
+pub fn retry<T, F>(settings: &Settings, mut operation: F) -> Result<T, String> where F: FnMut() -> Result<T, Failure> {
+    let mut retries = 0;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(ref failure) if failure.is_transient && retries < settings.retries => {
+                retries += 1;
+                thread::sleep(backoff_delay(settings, retries));
+            },
+            Err(failure) if retries > 0 => return Err(format!("{} (after {} attempts)", failure.message, retries + 1)),
+            Err(failure) => return Err(failure.message),
+        }
+    }
+}
+
+// Returns the host and port which a clone url connects to, local paths have none
+pub fn endpoint(url: &str) -> Option<(String, u16)> {
+    let (scheme, authority, is_scp) = match url.find("://") {
+        Some(index) => (&url[..index], url[index + 3..].split('/').next()?, false),
+        None => match (url.find(':'), url.find('/')) {
+            // `user@host:path`, unlike a drive letter
+            (Some(colon), slash) if colon > 1 && slash.map_or(true, |slash| colon < slash) => ("ssh", &url[..colon], true),
+            _ => return None,
+        },
+    };
+    let default_port = match scheme {
+        "http" => 80,
+        "https" => 443,
+        "ssh" | "git+ssh" | "ssh+git" => 22,
+        "git" => 9418,
+        _ => return None,
+    };
+    let authority = authority.rsplit('@').next()?;
+    let (host, port) = match authority.find(':') {
+        Some(colon) if !is_scp => (&authority[..colon], authority[colon + 1..].parse().ok()?),
+        _ => (authority, default_port),
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some((host.to_lowercase(), port))
+    }
+}
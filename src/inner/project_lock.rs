@@ -0,0 +1,161 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use inner::config::PROJECT_CONFIG_DIR;
+use inner::logger::Logger;
+#[cfg(unix)]
+use libc;
+#[cfg(windows)]
+use winapi;
+#[cfg(windows)]
+use kernel32;
+
+pub const LOCK_FILE: &str = "lock";
+// The commands which change `rubigo.json`, `rubigo.lock` or `vendor` directory
+pub const LOCKED_COMMANDS: &[&str] = &["apply", "get", "init", "import", "export", "remove", "reset", "tidy", "update"];
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn lock_path() -> PathBuf {
+    Path::new(PROJECT_CONFIG_DIR).join(LOCK_FILE)
+}
+
+// The lock is an OS lock of the file, which is released when the process exits for any reason.
+// The file only contains the process id of its owner to be displayed by other processes.
+#[derive(Debug)]
+pub struct ProjectLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        remove_locked(self.path.as_path(), &self.file);
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Attempt {
+    Locked(ProjectLock),
+    Held(Option<u32>),
+}
+
+pub fn try_lock(path: &Path) -> Result<Attempt, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("unable to create `{}`: {}", dir.to_string_lossy(), e))?;
+    }
+    loop {
+        // The file is truncated only when it is locked, the process id of its owner is kept otherwise
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(|e| format!("unable to open `{}`: {}", path.to_string_lossy(), e))?;
+        if !lock_file(&file).map_err(|e| format!("unable to lock `{}`: {}", path.to_string_lossy(), e))? {
+            return Ok(Attempt::Held(read_pid(path)))
+        }
+        // The previous owner might have removed the file after it was opened
+        if !is_same_file(path, &file) {
+            continue
+        }
+        file.set_len(0).and_then(|_| file.write_all(format!("{}\n", process::id()).as_bytes())).map_err(|e| format!("unable to write `{}`: {}", path.to_string_lossy(), e))?;
+        return Ok(Attempt::Locked(ProjectLock {
+            path: path.to_path_buf(),
+            file,
+        }))
+    }
+}
+
+// Waits until the lock is released, or fails if `should_wait` is false
+pub fn lock(path: &Path, should_wait: bool, logger: Logger) -> Result<ProjectLock, String> {
+    let mut is_waiting = false;
+    loop {
+        let pid = match try_lock(path)? {
+            Attempt::Locked(lock) => return Ok(lock),
+            Attempt::Held(pid) => pid,
+        };
+        let owner = match pid {
+            Some(pid) => format!("another Rubigo process ({})", pid),
+            None => String::from("another Rubigo process"),
+        };
+        if !should_wait {
+            return Err(format!("the project is locked by {}", owner))
+        }
+        if !is_waiting {
+            logger.info("Blocking", format!("waiting for {} to release `{}`", owner, path.to_string_lossy()));
+            is_waiting = true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut contents)).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(unix)]
+fn lock_file(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true)
+    }
+    let e = io::Error::last_os_error();
+    if e.kind() == io::ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(e)
+    }
+}
+
+// The locked byte is beyond the process id, so other processes could still read it
+#[cfg(windows)]
+fn lock_file(file: &File) -> io::Result<bool> {
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    let mut overlapped: winapi::OVERLAPPED = unsafe { mem::zeroed() };
+    overlapped.OffsetHigh = 1;
+    let flags = winapi::LOCKFILE_EXCLUSIVE_LOCK | winapi::LOCKFILE_FAIL_IMMEDIATELY;
+    if unsafe { kernel32::LockFileEx(file.as_raw_handle() as winapi::HANDLE, flags, 0, 1, 0, &mut overlapped) } != 0 {
+        return Ok(true)
+    }
+    let e = io::Error::last_os_error();
+    if e.raw_os_error() == Some(winapi::ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(e)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_file(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn is_same_file(path: &Path, file: &File) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(path), file.metadata()) {
+        (Ok(path_metadata), Ok(file_metadata)) => path_metadata.dev() == file_metadata.dev() && path_metadata.ino() == file_metadata.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_path: &Path, _file: &File) -> bool {
+    true
+}
+
+// The file is removed while it is still locked, so a process which has opened it opens it again
+#[cfg(unix)]
+fn remove_locked(path: &Path, _file: &File) {
+    let _ = fs::remove_file(path);
+}
+
+// An open file could not be replaced on Windows, so it is only emptied
+#[cfg(not(unix))]
+fn remove_locked(_path: &Path, file: &File) {
+    let _ = file.set_len(0);
+}
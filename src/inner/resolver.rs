@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt;
+use semver::{Version, VersionReq};
+use regex::Regex;
+use inner::manifest::GitPackage;
+
+pub const ROOT_REQUIRER: &str = "rubigo.json";
+const MAX_STEPS: usize = 10_000;
+
+pub trait Source {
+    fn tags(&mut self, pkg: &GitPackage) -> Result<Vec<String>, String>;
+    fn dependencies(&mut self, pkg: &GitPackage, revision: &str) -> Result<Vec<GitPackage>, String>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub import: String,
+    pub repo: Option<String>,
+    pub vcs: Option<String>,
+    pub revision: String,
+    pub version: Option<Version>,
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub requirer: String,
+    pub constraint: String,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    NoVersion {
+        import: String,
+        requirements: Vec<Requirement>,
+        available: Vec<String>,
+    },
+    Source {
+        import: String,
+        message: String,
+    },
+    TooComplex,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Conflict::NoVersion { ref import, ref requirements, ref available } => {
+                write!(f, "no version of `{}` satisfies every constraint", import)?;
+                for req in requirements {
+                    write!(f, "\n  `{}` requires `{}`", req.requirer, req.constraint)?;
+                    if !req.exclude.is_empty() {
+                        write!(f, " excluding {}", req.exclude.join(", "))?;
+                    }
+                }
+                if available.is_empty() {
+                    write!(f, "\n  no tag of `{}` is a semantic version", import)
+                } else {
+                    write!(f, "\n  available versions: {}", available.join(", "))
+                }
+            },
+            Conflict::Source { ref import, ref message } => write!(f, "unable to resolve `{}`: {}", import, message),
+            Conflict::TooComplex => write!(f, "gave up resolving the dependencies after {} attempts", MAX_STEPS),
+        }
+    }
+}
+
+pub fn tag_version(tag_name: &str) -> Option<Version> {
+    let re = match Regex::new(r"^v?([0-9]+)[.]?([0-9]*)[.]?([0-9]*)([-]?.*)") {
+        Ok(re) => re,
+        _ => return None,
+    };
+    let caps = re.captures(tag_name)?;
+    let part = |index: usize| match caps.get(index) {
+        Some(c) if !c.as_str().is_empty() => c.as_str(),
+        _ => "0",
+    };
+    let rest = match caps.get(4) {
+        Some(c) => c.as_str(),
+        _ => "",
+    };
+    Version::parse(format!("{}.{}.{}{}", part(1), part(2), part(3), rest).as_str()).ok()
+}
+
+pub fn is_excluded(tag_name: &str, version: &Version, excluded: &[String]) -> bool {
+    excluded.iter().any(|ex| ex == tag_name || tag_version(ex).as_ref() == Some(version))
+}
+
+#[derive(Clone, Default)]
+struct State {
+    order: Vec<String>,
+    remotes: HashMap<String, GitPackage>,
+    parents: HashMap<String, Option<String>>,
+    requirements: HashMap<String, Vec<Requirement>>,
+    decisions: HashMap<String, Decision>,
+}
+
+impl State {
+    fn require(&mut self, pkg: &GitPackage, requirer: &str) {
+        if !self.requirements.contains_key(&pkg.import) {
+            self.order.push(pkg.import.clone());
+            self.remotes.insert(pkg.import.clone(), pkg.clone());
+            self.parents.insert(pkg.import.clone(), if requirer == ROOT_REQUIRER {
+                None
+            } else {
+                Some(requirer.to_owned())
+            });
+        }
+        self.requirements.entry(pkg.import.clone()).or_default().push(Requirement {
+            requirer: requirer.to_owned(),
+            constraint: pkg.version.clone(),
+            exclude: pkg.exclude.clone(),
+        });
+    }
+}
+
+fn satisfies(decision: &Decision, requirements: &[Requirement]) -> bool {
+    let version = match decision.version {
+        Some(ref version) => version,
+        None => return true,
+    };
+    requirements.iter().all(|req| match VersionReq::parse(req.constraint.as_str()) {
+        Ok(rule) => rule.matches(version) && !is_excluded(decision.revision.as_str(), version, &req.exclude),
+        _ => req.constraint == decision.revision,
+    })
+}
+
+struct Resolver<'a, S: Source + 'a> {
+    source: &'a mut S,
+    tags: HashMap<String, Vec<String>>,
+    dependencies: HashMap<(String, String), Vec<GitPackage>>,
+    steps: usize,
+    conflict: Option<Conflict>,
+}
+
+impl<'a, S: Source> Resolver<'a, S> {
+    fn search(&mut self, state: State) -> Option<State> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            self.conflict = Some(Conflict::TooComplex);
+            return None
+        }
+
+        let import = match state.order.iter().find(|import| !state.decisions.contains_key(*import)) {
+            Some(import) => import.clone(),
+            None => return Some(state),
+        };
+        let remote = state.remotes[&import].clone();
+
+        for (revision, version) in self.candidates(&state, &remote)? {
+            let deps = match self.dependencies(&remote, revision.as_str()) {
+                Ok(deps) => deps,
+                Err(message) => {
+                    self.conflict = Some(Conflict::Source {
+                        import: import.clone(),
+                        message,
+                    });
+                    continue
+                },
+            };
+
+            let mut next = state.clone();
+            next.decisions.insert(import.clone(), Decision {
+                import: import.clone(),
+                repo: remote.repo.clone(),
+                vcs: remote.vcs.clone(),
+                revision,
+                version,
+                parent: state.parents.get(&import).cloned().unwrap_or(None),
+            });
+
+            let mut is_compatible = true;
+            for dep in deps.iter().filter(|dep| dep.import != import) {
+                next.require(dep, import.as_str());
+                if let Some(decision) = next.decisions.get(&dep.import) {
+                    if !satisfies(decision, &next.requirements[&dep.import]) {
+                        self.conflict = Some(Conflict::NoVersion {
+                            import: dep.import.clone(),
+                            requirements: next.requirements[&dep.import].clone(),
+                            available: vec![decision.revision.clone()],
+                        });
+                        is_compatible = false;
+                        break
+                    }
+                }
+            }
+
+            if is_compatible {
+                if let Some(resolved) = self.search(next) {
+                    return Some(resolved)
+                }
+                if let Some(Conflict::TooComplex) = self.conflict {
+                    return None
+                }
+            }
+        }
+        None
+    }
+
+    fn candidates(&mut self, state: &State, remote: &GitPackage) -> Option<Vec<(String, Option<Version>)>> {
+        let import = remote.import.as_str();
+        let requirements = &state.requirements[import];
+
+        // A revision in `rubigo.json` pins the package regardless of the other constraints
+        if let Some(req) = requirements.iter().find(|req| req.requirer == ROOT_REQUIRER && VersionReq::parse(req.constraint.as_str()).is_err()) {
+            return Some(vec![(req.constraint.clone(), None)])
+        }
+        let mut revisions: Vec<&str> = requirements.iter().filter(|req| VersionReq::parse(req.constraint.as_str()).is_err()).map(|req| req.constraint.as_str()).collect();
+        revisions.sort();
+        revisions.dedup();
+        match revisions.len() {
+            0 => (),
+            1 => return Some(vec![(revisions[0].to_owned(), None)]),
+            _ => {
+                self.conflict = Some(Conflict::NoVersion {
+                    import: import.to_owned(),
+                    requirements: requirements.clone(),
+                    available: Vec::new(),
+                });
+                return None
+            },
+        }
+
+        if !self.tags.contains_key(import) {
+            match self.source.tags(remote) {
+                Ok(tags) => {
+                    self.tags.insert(import.to_owned(), tags);
+                },
+                Err(message) => {
+                    self.conflict = Some(Conflict::Source {
+                        import: import.to_owned(),
+                        message,
+                    });
+                    return None
+                },
+            }
+        }
+
+        let mut available: Vec<(String, Version)> = self.tags[import].iter().filter_map(|tag| tag_version(tag).map(|version| (tag.clone(), version))).collect();
+        available.sort_by(|a, b| b.1.cmp(&a.1));
+        let candidates: Vec<(String, Option<Version>)> = available.iter().filter(|(tag, version)| {
+            requirements.iter().all(|req| match VersionReq::parse(req.constraint.as_str()) {
+                Ok(rule) => rule.matches(version) && !is_excluded(tag.as_str(), version, &req.exclude),
+                _ => true,
+            })
+        }).map(|(tag, version)| (tag.clone(), Some(version.clone()))).collect();
+
+        if candidates.is_empty() {
+            self.conflict = Some(Conflict::NoVersion {
+                import: import.to_owned(),
+                requirements: requirements.clone(),
+                available: available.iter().rev().map(|a| a.0.clone()).collect(),
+            });
+            return None
+        }
+        Some(candidates)
+    }
+
+    fn dependencies(&mut self, remote: &GitPackage, revision: &str) -> Result<Vec<GitPackage>, String> {
+        let key = (remote.import.clone(), revision.to_owned());
+        if !self.dependencies.contains_key(&key) {
+            let deps = self.source.dependencies(remote, revision)?;
+            self.dependencies.insert(key.clone(), deps);
+        }
+        Ok(self.dependencies[&key].clone())
+    }
+}
+
+pub fn resolve<S: Source>(source: &mut S, roots: &[GitPackage], locked: &[Decision]) -> Result<Vec<Decision>, Conflict> {
+    let mut state = State::default();
+    for decision in locked {
+        state.decisions.insert(decision.import.clone(), decision.clone());
+    }
+    for pkg in roots {
+        state.require(pkg, ROOT_REQUIRER);
+        if let Some(decision) = state.decisions.get(&pkg.import) {
+            if !satisfies(decision, &state.requirements[&pkg.import]) {
+                return Err(Conflict::NoVersion {
+                    import: pkg.import.clone(),
+                    requirements: state.requirements[&pkg.import].clone(),
+                    available: vec![decision.revision.clone()],
+                })
+            }
+        }
+    }
+
+    let mut resolver = Resolver {
+        source,
+        tags: HashMap::new(),
+        dependencies: HashMap::new(),
+        steps: 0,
+        conflict: None,
+    };
+    match resolver.search(state) {
+        Some(resolved) => Ok(resolved.order.iter().filter(|import| !locked.iter().any(|d| d.import == **import)).map(|import| resolved.decisions[import].clone()).collect()),
+        None => Err(resolver.conflict.unwrap_or(Conflict::TooComplex)),
+    }
+}
@@ -0,0 +1,218 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use json::{self, JsonValue};
+use inner::config::PROJECT_CONFIG_DIR;
+use inner::{helpers, json_helper};
+use inner::logger::Logger;
+use inner::vendor::VENDOR_DIR;
+
+pub const STAGING_DIR: &str = "staging";
+pub const LOCK_FILE: &str = "rubigo.lock";
+// The packages which are replaced or removed, written along with the staged lock
+pub const PACKAGES_FILE: &str = "packages.json";
+// Written once the staged files are complete, so an interrupted swap is finished instead of discarded
+pub const COMMIT_FILE: &str = "commit";
+pub const OLD_VENDOR_DIR: &str = "vendor.old";
+
+pub fn staging_path() -> PathBuf {
+    Path::new(PROJECT_CONFIG_DIR).join(STAGING_DIR)
+}
+
+// The packages which are changed are copied first, they replace the ones of `vendor` directory with the new lock only if everything succeeds
+pub struct Staging {
+    dir_path: PathBuf,
+    vendor_path: PathBuf,
+    imports: Vec<String>,
+    is_committed: bool,
+}
+
+impl Staging {
+    pub fn begin(imports: Vec<String>, vendor_path: &Path, logger: Logger) -> Result<Staging, String> {
+        recover(vendor_path, logger)?;
+        let dir_path = staging_path();
+        let staging = Staging {
+            dir_path: dir_path.clone(),
+            vendor_path: vendor_path.to_path_buf(),
+            imports,
+            is_committed: false,
+        };
+        let staged_vendor = staging.vendor_dir();
+        fs::create_dir_all(staged_vendor.as_path()).map_err(|e| format!("unable to create `{}`: {}", staged_vendor.to_string_lossy(), e))?;
+        for import in staging.imports.iter() {
+            let pkg_path = helpers::get_path_from_url(import, vendor_path);
+            if !pkg_path.is_dir() {
+                continue
+            }
+            copy_dir(pkg_path.as_path(), helpers::get_path_from_url(import, staged_vendor.as_path()).as_path(), false).map_err(|e| format!("unable to stage `{}`: {}", import, e))?;
+            logger.verbose("Stage package", import);
+        }
+        Ok(staging)
+    }
+
+    // The directory which packages are installed in until the staging is committed
+    pub fn vendor_dir(&self) -> PathBuf {
+        self.dir_path.join(VENDOR_DIR)
+    }
+
+    pub fn commit(mut self, lock: JsonValue, logger: Logger) -> Result<(), String> {
+        let staged_vendor = self.vendor_dir();
+        let mut imports = self.imports.clone();
+        for import in lock_imports(&lock) {
+            if !imports.contains(&import) && helpers::get_path_from_url(import.as_str(), staged_vendor.as_path()).is_dir() {
+                imports.push(import);
+            }
+        }
+        let staged_lock = self.dir_path.join(LOCK_FILE);
+        let packages_path = self.dir_path.join(PACKAGES_FILE);
+        json_helper::write(staged_lock.as_path(), "", Some(lock))
+            .and_then(|_| File::open(staged_lock.as_path())?.sync_all())
+            .and_then(|_| json_helper::write(packages_path.as_path(), "", Some(imports.clone().into())))
+            .and_then(|_| File::open(packages_path.as_path())?.sync_all())
+            .and_then(|_| File::create(self.dir_path.join(COMMIT_FILE))?.sync_all())
+            .map_err(|e| format!("unable to stage `{}`: {}", LOCK_FILE, e))?;
+        self.is_committed = true;
+        finish(self.dir_path.as_path(), self.vendor_path.as_path(), &imports)?;
+        logger.verbose("Update directory", VENDOR_DIR);
+        logger.verbose("Update file", LOCK_FILE);
+        Ok(())
+    }
+
+    // Discards the staged changes, the same as dropping the staging
+    pub fn rollback(self) {}
+}
+
+// A process which is killed (e.g. by Ctrl-C) does not drop its staging, which is removed by `recover` instead
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if !self.is_committed {
+            let _ = fs::remove_dir_all(self.dir_path.as_path());
+        }
+    }
+}
+
+// The git and local packages of a lock (or the `packages` of `rubigo.json`), which are the directories of `vendor` directory
+pub fn lock_imports(lock: &JsonValue) -> Vec<String> {
+    let git_imports = lock[json_helper::GIT_KEY].members().filter_map(|pkg| pkg[json_helper::IMPORT_KEY].as_str()).map(helpers::strip_url_scheme);
+    let local_imports = lock[json_helper::LOCAL_KEY].members().filter_map(|pkg| pkg.as_str()).map(|pkg| pkg.to_owned());
+    let mut imports: Vec<String> = Vec::new();
+    for import in git_imports.chain(local_imports) {
+        if !imports.contains(&import) {
+            imports.push(import);
+        }
+    }
+    imports
+}
+
+// Finishes the swap of a committed staging, or removes a staging which was interrupted before its commit
+pub fn recover(vendor_path: &Path, logger: Logger) -> Result<(), String> {
+    let dir_path = staging_path();
+    if !dir_path.exists() {
+        return Ok(())
+    }
+    if dir_path.join(COMMIT_FILE).is_file() {
+        let packages_path = dir_path.join(PACKAGES_FILE);
+        let imports = fs::read_to_string(packages_path.as_path()).map_err(|e| e.to_string()).and_then(|contents| json::parse(contents.as_str()).map_err(|e| e.to_string()))
+            .map_err(|e| format!("unable to read `{}`: {}", packages_path.to_string_lossy(), e))?;
+        let imports: Vec<String> = imports.members().filter_map(|import| import.as_str()).map(|import| import.to_owned()).collect();
+        finish(dir_path.as_path(), vendor_path, &imports)?;
+        logger.info("Recovered", format!("finished the interrupted update of `{}` and `{}`", VENDOR_DIR, LOCK_FILE));
+        return Ok(())
+    }
+    fs::remove_dir_all(dir_path.as_path()).map_err(|e| format!("unable to remove `{}`: {}", dir_path.to_string_lossy(), e))?;
+    logger.verbose("Remove directory", dir_path.to_string_lossy());
+    Ok(())
+}
+
+// Every step could be repeated after an interruption, a package which has been moved aside is not moved again
+fn finish(dir_path: &Path, vendor_path: &Path, imports: &[String]) -> Result<(), String> {
+    let staged_vendor = dir_path.join(VENDOR_DIR);
+    let old_vendor = dir_path.join(OLD_VENDOR_DIR);
+    let staged_lock = dir_path.join(LOCK_FILE);
+    let swap = || -> io::Result<()> {
+        for import in imports {
+            let pkg_path = helpers::get_path_from_url(import, vendor_path);
+            let staged_pkg_path = helpers::get_path_from_url(import, staged_vendor.as_path());
+            let old_pkg_path = helpers::get_path_from_url(import, old_vendor.as_path());
+            if !old_pkg_path.exists() {
+                if pkg_path.exists() {
+                    old_pkg_path.parent().map_or(Ok(()), fs::create_dir_all)?;
+                    fs::rename(pkg_path.as_path(), old_pkg_path.as_path())?;
+                } else {
+                    fs::create_dir_all(old_pkg_path.as_path())?;
+                }
+            }
+            if staged_pkg_path.exists() {
+                pkg_path.parent().map_or(Ok(()), fs::create_dir_all)?;
+                fs::rename(staged_pkg_path.as_path(), pkg_path.as_path())?;
+            } else {
+                remove_empty_parents(pkg_path.as_path(), vendor_path);
+            }
+        }
+        if staged_vendor.is_dir() {
+            move_new(staged_vendor.as_path(), vendor_path)?;
+        }
+        if staged_lock.is_file() {
+            fs::rename(staged_lock.as_path(), LOCK_FILE)?;
+        }
+        fs::remove_file(dir_path.join(COMMIT_FILE))
+    };
+    swap().map_err(|e| format!("unable to replace `{}` and `{}` with the staged ones: {}", VENDOR_DIR, LOCK_FILE, e))?;
+    let _ = fs::remove_dir_all(dir_path);
+    Ok(())
+}
+
+// Moves the packages which were installed for the first time, the other directories only contain packages which have been replaced
+fn move_new(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let (source_path, target_path) = (entry.path(), target.join(entry.file_name()));
+        if !target_path.exists() {
+            fs::rename(source_path.as_path(), target_path.as_path())?;
+        } else if entry.file_type()?.is_dir() {
+            move_new(source_path.as_path(), target_path.as_path())?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_empty_parents(path: &Path, root: &Path) {
+    let mut parent = path.parent();
+    while let Some(dir_path) = parent {
+        if dir_path == root || fs::remove_dir(dir_path).is_err() {
+            break
+        }
+        parent = dir_path.parent();
+    }
+}
+
+// The object database of git is never changed in place, so its files are shared instead of copied
+pub fn copy_dir(source: &Path, target: &Path, is_immutable: bool) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let (source_path, target_path) = (entry.path(), target.join(entry.file_name()));
+        if file_type.is_dir() {
+            let is_objects = entry.file_name() == "objects" && source.file_name() == Some(OsStr::new(".git"));
+            copy_dir(source_path.as_path(), target_path.as_path(), is_immutable || is_objects)?;
+        } else if file_type.is_symlink() {
+            copy_link(source_path.as_path(), target_path.as_path())?;
+        } else if !is_immutable || fs::hard_link(source_path.as_path(), target_path.as_path()).is_err() {
+            fs::copy(source_path.as_path(), target_path.as_path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn copy_link(source: &Path, target: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(fs::read_link(source)?, target)
+}
+
+#[cfg(not(unix))]
+pub fn copy_link(source: &Path, target: &Path) -> io::Result<()> {
+    fs::copy(source, target).map(|_| ())
+}
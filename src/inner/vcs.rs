@@ -0,0 +1,504 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::fs::{File, create_dir_all, remove_dir_all};
+use std::io::{self, Read, Write};
+use git2::{Repository, ResetType, ObjectType};
+use semver::{Version, VersionReq};
+use json::JsonValue;
+use inner::context::Context;
+use inner::logger::Logger;
+use inner::{cache, git_helper, goget, jobs, modproxy, network, resolver};
+use inner::network::Failure;
+
+pub const KINDS: &[Kind] = &[Kind::Git, Kind::Hg, Kind::Mod];
+pub const VCS_DIRS: &[&str] = &[".git", ".hg", modproxy::META_DIR];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Git,
+    Hg,
+    Mod,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Kind::Git => "git",
+            Kind::Hg => "hg",
+            Kind::Mod => modproxy::VCS_NAME,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        KINDS.iter().cloned().find(|kind| kind.name() == name)
+    }
+
+    pub fn from_option(name: Option<&str>) -> Kind {
+        name.and_then(Kind::from_name).unwrap_or(Kind::Git)
+    }
+}
+
+pub trait Vcs {
+    fn fetch(&self) -> Result<(), String>;
+    fn tags(&self) -> Result<Vec<String>, String>;
+    fn resolve_revision(&self, revision: &str) -> Option<String>;
+    fn checkout(&self, commit: &str) -> Result<(), String>;
+    fn latest_commit(&self) -> Option<String>;
+    fn current_branch(&self) -> Option<String>;
+    fn set_origin(&self, url: &str) -> Result<(), String>;
+    fn read_file(&self, commit: &str, file_path: &str) -> Option<Vec<u8>>;
+
+    // The hash of the downloaded archive, if the files are not checked out from a repository
+    fn sum(&self) -> Option<String> {
+        None
+    }
+}
+
+// Clones from the mirror in the user cache if it is enabled, the origin of the clone is still `url`
+pub fn clone(kind: Kind, url: &str, dir_path: &Path, ctx: &Context, logger: Logger) -> Result<Box<dyn Vcs>, String> {
+    let mirror_path = mirror_or_remote(kind, url, ctx, logger)?;
+    let source = match mirror_path {
+        Some(ref mirror_path) => mirror_path.to_string_lossy().into_owned(),
+        None if ctx.offline => return Err(format!("`{}` is not in the cache and could not be cloned offline", url)),
+        None => url.to_owned(),
+    };
+    let repository: Box<dyn Vcs> = match kind {
+        Kind::Git => {
+            let repo = if mirror_path.is_some() {
+                git_helper::clone_local(source.as_str(), dir_path).map_err(|e| e.to_string())?
+            } else {
+                git_helper::clone(url, dir_path, false, &ctx.config)?
+            };
+            Box::new(GitRepository { repo, ctx: ctx.clone(), logger })
+        },
+        Kind::Hg => {
+            run_hg_remote(source.as_str(), None, &["clone", "--noupdate", "--", source.as_str(), dir_path.to_string_lossy().as_ref()], &ctx.config)?;
+            Box::new(HgRepository { path: dir_path.to_path_buf(), ctx: ctx.clone(), logger })
+        },
+        Kind::Mod => Box::new(ModRepository { module: modproxy::Module::create(url, dir_path, ctx)? }),
+    };
+    if mirror_path.is_some() {
+        repository.set_origin(url)?;
+    }
+    Ok(repository)
+}
+
+// A mirror which could not be updated is skipped, except offline where the cache is the only source
+fn mirror_or_remote(kind: Kind, url: &str, ctx: &Context, logger: Logger) -> Result<Option<PathBuf>, String> {
+    match update_mirror(kind, url, ctx) {
+        Err(ref e) if !ctx.offline => {
+            logger.error(format!("unable to update the mirror of `{}`, it is fetched directly: {}", url, e));
+            Ok(None)
+        },
+        result => result,
+    }
+}
+
+pub fn update_mirror(kind: Kind, url: &str, ctx: &Context) -> Result<Option<PathBuf>, String> {
+    let mirror_path = match cache::mirror_path(ctx.cache_dir.as_deref(), kind.name(), url) {
+        Some(mirror_path) => mirror_path,
+        None => return Ok(None),
+    };
+    let is_new = !mirror_path.is_dir();
+    if ctx.offline {
+        return Ok(if is_new {
+            None
+        } else {
+            Some(mirror_path)
+        })
+    }
+    if is_new {
+        if let Some(parent) = mirror_path.parent() {
+            create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+    }
+    let result = match kind {
+        Kind::Git => if is_new {
+            git_helper::clone(url, mirror_path.as_path(), true, &ctx.config)
+        } else {
+            Repository::open(mirror_path.as_path()).map_err(|e| e.to_string())
+        }.and_then(|mirror| git_helper::fetch_mirror(&mirror, &ctx.config)),
+        Kind::Hg if is_new => run_hg_remote(url, None, &["clone", "--noupdate", "--", url, mirror_path.to_string_lossy().as_ref()], &ctx.config).map(|_| ()),
+        Kind::Hg => run_hg_remote(url, Some(mirror_path.as_path()), &["pull"], &ctx.config).map(|_| ()),
+        // The files of a module are cached as they are downloaded
+        Kind::Mod => create_dir_all(mirror_path.as_path()).map_err(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        if is_new {
+            let _ = remove_dir_all(mirror_path.as_path());
+        }
+        return Err(e)
+    }
+    cache::touch(mirror_path.as_path()).map_err(|e| e.to_string())?;
+    Ok(Some(mirror_path))
+}
+
+pub fn open(kind: Kind, dir_path: &Path, ctx: &Context, logger: Logger) -> Result<Box<dyn Vcs>, String> {
+    match kind {
+        Kind::Git => match Repository::open(dir_path) {
+            Ok(repo) => Ok(Box::new(GitRepository { repo, ctx: ctx.clone(), logger })),
+            Err(e) => Err(e.to_string()),
+        },
+        Kind::Hg => if dir_path.join(".hg").is_dir() {
+            Ok(Box::new(HgRepository { path: dir_path.to_path_buf(), ctx: ctx.clone(), logger }))
+        } else {
+            Err(format!("`{}` is not a mercurial repository", dir_path.to_string_lossy()))
+        },
+        Kind::Mod => Ok(Box::new(ModRepository { module: modproxy::Module::open(dir_path, ctx)? })),
+    }
+}
+
+pub fn latest_version(tag_names: &[String], version_rule: Option<&VersionReq>, excluded: &[String]) -> Option<(String, Version)> {
+    let mut version: Option<(String, Version)> = None;
+    for tag_name in tag_names {
+        let tag_version = match resolver::tag_version(tag_name) {
+            Some(ver) => ver,
+            None => continue,
+        };
+        if resolver::is_excluded(tag_name, &tag_version, excluded) {
+            continue
+        }
+        if version_rule.map_or(true, |rule| rule.matches(&tag_version)) && version.as_ref().map_or(true, |selected| tag_version > selected.1) {
+            version = Some((tag_name.to_owned(), tag_version));
+        }
+    }
+    version
+}
+
+pub fn latest_compat_version(vcs: &dyn Vcs, rule_tag_name: String, excluded: &[String]) -> String {
+    match VersionReq::parse(rule_tag_name.as_str()) {
+        Ok(version_rule) => match latest_version(&vcs.tags().unwrap_or_default(), Some(&version_rule), excluded) {
+            Some((tag_name, _)) => tag_name,
+            None => rule_tag_name,
+        },
+        _ => rule_tag_name,
+    }
+}
+
+// Falls back to the latest commit if the revision no longer exists, returns the commit and the version
+pub fn resolve_or_latest(vcs: &dyn Vcs, pkg_import: &str, version: String, logger: Logger) -> Option<(String, String)> {
+    if let Some(commit) = vcs.resolve_revision(version.as_str()) {
+        return Some((commit, version))
+    }
+    let commit = vcs.latest_commit()?;
+    logger.error(format!("the version of `{}` changed to `{}` due to missing revision `{}`", pkg_import, commit, version));
+    Some((commit.clone(), commit))
+}
+
+// The major version in the path of a `gopkg.in` import restricts the tags and the default revision of its repository
+pub fn for_import(vcs: Box<dyn Vcs>, import: &str) -> Box<dyn Vcs> {
+    match goget::gopkg_in(import) {
+        Some(gopkg) => Box::new(MajorVersion { vcs, major: gopkg.major }),
+        None => vcs,
+    }
+}
+
+struct MajorVersion {
+    vcs: Box<dyn Vcs>,
+    major: u64,
+}
+
+impl MajorVersion {
+    fn branch(&self) -> Option<String> {
+        let name = format!("v{}", self.major);
+        self.vcs.resolve_revision(name.as_str()).map(|_| name)
+    }
+}
+
+impl Vcs for MajorVersion {
+    fn fetch(&self) -> Result<(), String> {
+        self.vcs.fetch()
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        Ok(self.vcs.tags()?.into_iter().filter(|tag| tag.starts_with('v') && resolver::tag_version(tag).map_or(false, |version| version.major == self.major)).collect())
+    }
+
+    fn resolve_revision(&self, revision: &str) -> Option<String> {
+        self.vcs.resolve_revision(revision)
+    }
+
+    fn checkout(&self, commit: &str) -> Result<(), String> {
+        self.vcs.checkout(commit)
+    }
+
+    // Like gopkg.in, `v0` falls back to the default branch if no tag or branch matches
+    fn latest_commit(&self) -> Option<String> {
+        if let Some((tag_name, _)) = latest_version(&self.tags().unwrap_or_default(), None, &[]) {
+            return self.vcs.resolve_revision(tag_name.as_str())
+        }
+        match self.branch() {
+            Some(branch) => self.vcs.resolve_revision(branch.as_str()),
+            None if self.major == 0 => self.vcs.latest_commit(),
+            None => None,
+        }
+    }
+
+    fn current_branch(&self) -> Option<String> {
+        match self.branch() {
+            Some(branch) => Some(branch),
+            None if self.major == 0 => self.vcs.current_branch(),
+            None => None,
+        }
+    }
+
+    fn set_origin(&self, url: &str) -> Result<(), String> {
+        self.vcs.set_origin(url)
+    }
+
+    fn read_file(&self, commit: &str, file_path: &str) -> Option<Vec<u8>> {
+        self.vcs.read_file(commit, file_path)
+    }
+
+    fn sum(&self) -> Option<String> {
+        self.vcs.sum()
+    }
+}
+
+struct GitRepository {
+    repo: Repository,
+    ctx: Context,
+    logger: Logger,
+}
+
+impl Vcs for GitRepository {
+    fn fetch(&self) -> Result<(), String> {
+        let mirror_path = match git_helper::origin_url(&self.repo) {
+            Some(url) => mirror_or_remote(Kind::Git, url.as_str(), &self.ctx, self.logger)?,
+            None => None,
+        };
+        match mirror_path {
+            Some(mirror_path) => git_helper::fetch_from(&self.repo, mirror_path.to_string_lossy().as_ref()).map_err(|e| e.to_string()),
+            None if self.ctx.offline => Ok(()),
+            None => git_helper::fetch_remote(&self.repo, &self.ctx.config),
+        }?;
+        git_helper::update_branches(&self.repo);
+        Ok(())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        match self.repo.tag_names(None) {
+            Ok(tag_names) => Ok(tag_names.iter().flatten().map(|tag| tag.to_owned()).collect()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn resolve_revision(&self, revision: &str) -> Option<String> {
+        git_helper::resolve_commit(&self.repo, revision)
+    }
+
+    fn checkout(&self, commit: &str) -> Result<(), String> {
+        let commit_object = self.repo.revparse_single(commit).and_then(|obj| obj.peel(ObjectType::Commit)).map_err(|e| e.to_string())?;
+        self.repo.set_head_detached(commit_object.id()).map_err(|e| e.to_string())?;
+        self.repo.reset(&commit_object, ResetType::Hard, Some(&mut git_helper::checkout_options())).map_err(|e| e.to_string())
+    }
+
+    fn latest_commit(&self) -> Option<String> {
+        git_helper::get_latest_commit(&self.repo)
+    }
+
+    fn current_branch(&self) -> Option<String> {
+        git_helper::get_current_branch(&self.repo)
+    }
+
+    fn set_origin(&self, url: &str) -> Result<(), String> {
+        self.repo.remote_set_url("origin", url).map_err(|e| e.to_string())
+    }
+
+    fn read_file(&self, commit: &str, file_path: &str) -> Option<Vec<u8>> {
+        let tree = self.repo.revparse_single(commit).and_then(|obj| obj.peel(ObjectType::Tree)).ok()?;
+        let entry = tree.as_tree()?.get_path(Path::new(file_path)).ok()?;
+        let blob = entry.to_object(&self.repo).ok()?.into_blob().ok()?;
+        Some(blob.content().to_vec())
+    }
+}
+
+struct HgRepository {
+    path: PathBuf,
+    ctx: Context,
+    logger: Logger,
+}
+
+impl HgRepository {
+    fn run(&self, args: &[&str]) -> Result<Vec<u8>, String> {
+        run_hg(Some(self.path.as_path()), args)
+    }
+
+    fn output(&self, args: &[&str]) -> Option<String> {
+        let output = String::from_utf8(self.run(args).ok()?).ok()?;
+        let output = output.trim();
+        if output.is_empty() {
+            None
+        } else {
+            Some(output.to_owned())
+        }
+    }
+}
+
+impl Vcs for HgRepository {
+    fn fetch(&self) -> Result<(), String> {
+        let url = self.output(&["paths", "default"]);
+        let mirror_path = match url {
+            Some(ref url) => mirror_or_remote(Kind::Hg, url.as_str(), &self.ctx, self.logger)?,
+            None => None,
+        };
+        match mirror_path {
+            Some(mirror_path) => self.run(&["pull", "--", mirror_path.to_string_lossy().as_ref()]).map(|_| ()),
+            None if self.ctx.offline => Ok(()),
+            None => run_hg_remote(url.as_deref().unwrap_or_default(), Some(self.path.as_path()), &["pull"], &self.ctx.config).map(|_| ()),
+        }
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let output = self.run(&["tags", "--quiet"])?;
+        Ok(String::from_utf8_lossy(&output).lines().map(|tag| tag.trim()).filter(|tag| !tag.is_empty() && *tag != "tip").map(|tag| tag.to_owned()).collect())
+    }
+
+    fn resolve_revision(&self, revision: &str) -> Option<String> {
+        self.output(&["log", "--rev", revision, "--limit", "1", "--template", "{node}"])
+    }
+
+    fn checkout(&self, commit: &str) -> Result<(), String> {
+        self.run(&["update", "--clean", "--rev", commit]).map(|_| ())
+    }
+
+    fn latest_commit(&self) -> Option<String> {
+        self.resolve_revision("default").or_else(|| self.resolve_revision("tip"))
+    }
+
+    fn current_branch(&self) -> Option<String> {
+        self.output(&["branch"])
+    }
+
+    fn set_origin(&self, url: &str) -> Result<(), String> {
+        let hgrc_path = self.path.join(".hg").join("hgrc");
+        let mut contents = String::new();
+        match File::open(hgrc_path.as_path()) {
+            Ok(mut file) => {
+                file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.to_string()),
+        }
+        File::create(hgrc_path).and_then(|mut file| file.write_all(set_hg_default(contents.as_str(), url).as_bytes())).map_err(|e| e.to_string())
+    }
+
+    fn read_file(&self, commit: &str, file_path: &str) -> Option<Vec<u8>> {
+        self.run(&["cat", "--rev", commit, "--", file_path]).ok()
+    }
+}
+
+// The versions of a module are its tags and its commits
+struct ModRepository {
+    module: modproxy::Module,
+}
+
+impl Vcs for ModRepository {
+    fn fetch(&self) -> Result<(), String> {
+        self.module.refresh();
+        Ok(())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        self.module.versions()
+    }
+
+    fn resolve_revision(&self, revision: &str) -> Option<String> {
+        self.module.resolve(revision)
+    }
+
+    fn checkout(&self, commit: &str) -> Result<(), String> {
+        self.module.unpack(commit)
+    }
+
+    fn latest_commit(&self) -> Option<String> {
+        self.module.latest()
+    }
+
+    fn current_branch(&self) -> Option<String> {
+        None
+    }
+
+    fn set_origin(&self, url: &str) -> Result<(), String> {
+        self.module.set_url(url)
+    }
+
+    fn read_file(&self, commit: &str, file_path: &str) -> Option<Vec<u8>> {
+        self.module.read_file(commit, file_path)
+    }
+
+    fn sum(&self) -> Option<String> {
+        self.module.sum()
+    }
+}
+
+// Clones and pulls from `url` are retried on transient errors, `http.timeout` of Mercurial limits the wait for a response
+fn run_hg_remote(url: &str, dir_path: Option<&Path>, args: &[&str], config: &JsonValue) -> Result<Vec<u8>, String> {
+    let settings = network::settings(config)?;
+    let timeout = format!("http.timeout={}", settings.timeout.as_secs());
+    let mut remote_args = Vec::new();
+    if settings.timeout.as_secs() > 0 {
+        remote_args.extend_from_slice(&["--config", timeout.as_str()]);
+    }
+    remote_args.extend_from_slice(args);
+    network::retry(&settings, || {
+        let _permit = jobs::acquire(url, config).map_err(Failure::permanent)?;
+        run_hg(dir_path, &remote_args).map_err(|e| Failure {
+            is_transient: network::is_transient_message(e.as_str()),
+            message: e,
+        })
+    })
+}
+
+// Replaces only `default` of the `[paths]` section in the contents of hgrc, the other entries are kept
+pub fn set_hg_default(contents: &str, url: &str) -> String {
+    let entry = format!("default = {}", url);
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_paths = false;
+    let mut is_set = false;
+    let mut in_replaced = false;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if in_replaced && !trimmed.is_empty() && line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        in_replaced = false;
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if in_paths && !is_set {
+                lines.push(entry.clone());
+                is_set = true;
+            }
+            in_paths = trimmed[1..trimmed.len() - 1].trim() == "paths";
+        } else if in_paths && !is_set && !line.starts_with(char::is_whitespace) && trimmed.contains('=') && trimmed.split('=').next().map(|key| key.trim()) == Some("default") {
+            lines.push(entry.clone());
+            is_set = true;
+            in_replaced = true;
+            continue;
+        }
+        lines.push(line.to_owned());
+    }
+    if !is_set {
+        if !in_paths {
+            if lines.last().map_or(false, |line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(String::from("[paths]"));
+        }
+        lines.push(entry);
+    }
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+fn run_hg(dir_path: Option<&Path>, args: &[&str]) -> Result<Vec<u8>, String> {
+    let mut command = Command::new("hg");
+    if let Some(dir_path) = dir_path {
+        command.arg("--repository").arg(dir_path);
+    }
+    match command.args(args).output() {
+        Ok(ref output) if output.status.success() => Ok(output.stdout.clone()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        Err(e) => Err(format!("unable to run `hg`: {}", e)),
+    }
+}
@@ -1,18 +1,33 @@
-use std::path::{Path, Component};
-use std::fs::{read_dir, remove_dir_all, create_dir_all};
-use git2::{Repository, BranchType, ResetType};
+use std::path::{Path, PathBuf, Component};
+use std::fs::{File, copy, read_dir, remove_dir_all, create_dir_all, rename};
+use std::io::{self, Write};
+use std::fmt::Display;
+use std::{env, process};
+use git2::{Repository, ObjectType};
+use semver::Version;
 use std::ffi::OsStr;
 use json::JsonValue;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use inner::context::Context;
 use inner::logger::Logger;
-use inner::{git_helper, go, helpers, json_helper};
+use inner::{cache, config, go, goget, helpers, json_helper, integrity, importer, modproxy, resolver, staging, vcs};
+use inner::manifest::GitPackage;
+use inner::resolver::{Decision, Source};
+use inner::vcs::Vcs;
 
 pub const VENDOR_DIR: &'static str = "vendor";
 
-pub fn find_packages(logger: Logger) -> JsonValue {
+pub fn find_packages(ctx: &Context, logger: Logger) -> JsonValue {
     let packages = Arc::new(Mutex::new(array![]));
-    let pool = helpers::new_thread_pool();
+    let pool = match helpers::new_thread_pool(ctx) {
+        Ok(pool) => pool,
+        Err(e) => {
+            logger.fatal(e);
+            return array![]
+        },
+    };
     let (tx, rx) = channel();
     let counter = Arc::new(Mutex::new(0));
 
@@ -23,8 +38,9 @@ pub fn find_packages(logger: Logger) -> JsonValue {
     let cp_tx = tx.clone();
     let cp_counter = counter.clone();
     let cp_pkgs = packages.clone();
+    let cp_ctx = ctx.clone();
     pool.execute(move || {
-        parse_dir(String::from(VENDOR_DIR), cp_pkgs, cp_tx, cp_counter, logger);
+        parse_dir(cp_ctx.vendor_dir.to_string_lossy().into_owned(), cp_pkgs, cp_tx, cp_counter, &cp_ctx, logger);
     });
 
     while match counter.lock() {
@@ -45,8 +61,9 @@ pub fn find_packages(logger: Logger) -> JsonValue {
                     let cp_tx = tx.clone();
                     let cp_counter = counter.clone();
                     let cp_pkgs = packages.clone();
+                    let cp_ctx = ctx.clone();
                     pool.execute(move || {
-                        parse_dir(p, cp_pkgs, cp_tx, cp_counter, logger);
+                        parse_dir(p, cp_pkgs, cp_tx, cp_counter, &cp_ctx, logger);
                     });
                 },
                 None => (),
@@ -54,16 +71,56 @@ pub fn find_packages(logger: Logger) -> JsonValue {
             _ => (),
         }
     }
-    match Arc::try_unwrap(packages) {
+    let mut found_packages = match Arc::try_unwrap(packages) {
         Ok(pkgs_mut) => match pkgs_mut.into_inner() {
             Ok(pkgs_array) => pkgs_array,
             _ => array![],
         },
         _ => array![],
+    };
+    for pkg in find_stripped_packages(ctx, logger).members() {
+        if !found_packages.members().any(|found| found[json_helper::IMPORT_KEY] == pkg[json_helper::IMPORT_KEY]) {
+            let _ = found_packages.push(pkg.clone());
+        }
+    }
+    found_packages
+}
+
+// Packages without `.git` directories, such as stripped packages and modules, are only known by `rubigo.lock`
+fn find_stripped_packages(ctx: &Context, logger: Logger) -> JsonValue {
+    let mut packages = array![];
+    let lock_content = match json_helper::read_lock(Path::new("rubigo.lock")) {
+        Ok(content) => content,
+        Err(_) => return packages,
+    };
+    for pkg in lock_content[json_helper::GIT_KEY].members() {
+        let pkg_import = match pkg[json_helper::IMPORT_KEY].as_str() {
+            Some(import_str) => helpers::strip_url_scheme(import_str),
+            None => continue,
+        };
+        let (_, pkg_path_buf, kind) = match locate_package(pkg_import.as_str(), pkg[json_helper::REPO_KEY].as_str(), pkg[json_helper::VCS_KEY].as_str(), ctx) {
+            Ok(location) => location,
+            Err(_) => continue,
+        };
+        if !pkg_path_buf.is_dir() || (kind != vcs::Kind::Mod && vcs::open(kind, pkg_path_buf.as_path(), ctx, logger).is_ok()) {
+            continue
+        }
+        if let Some(hash) = pkg[json_helper::HASH_KEY].as_str() {
+            match integrity::hash_dir(pkg_path_buf.as_path()) {
+                Ok(ref actual) if actual == hash => (),
+                Ok(_) => logger.error(format!("the files of `{}` have been modified since they were locked", pkg_import)),
+                Err(e) => logger.error(format!("unable to hash `{}`: {}", pkg_import, e)),
+            }
+        }
+        logger.verbose("Find package", &pkg_import);
+        let mut stripped_pkg = pkg.clone();
+        stripped_pkg.remove(json_helper::PARENT_KEY);
+        let _ = packages.push(stripped_pkg);
     }
+    packages
 }
 
-pub fn install_local_packages(local_packages: &JsonValue, logger: Logger) -> JsonValue {
+pub fn install_local_packages(local_packages: &JsonValue, vendor_path: &Path, logger: Logger) -> JsonValue {
     let mut installed_packages = array![];
     if !local_packages.is_null() {
         for i in 0..local_packages.len() {
@@ -71,7 +128,7 @@ pub fn install_local_packages(local_packages: &JsonValue, logger: Logger) -> Jso
                 Some(val_str) => val_str,
                 None => continue,
             };
-            let dir_path = Path::new(VENDOR_DIR).join(local_pkg);
+            let dir_path = vendor_path.join(local_pkg);
             if !dir_path.is_dir() {
                 match create_dir_all(dir_path) {
                     Ok(_) => {
@@ -88,7 +145,8 @@ pub fn install_local_packages(local_packages: &JsonValue, logger: Logger) -> Jso
     installed_packages
 }
 
-pub fn install_global_packages(global_packages: &JsonValue, should_update: bool, logger: Logger) -> JsonValue {
+// `go get` of every package writes to the same GOPATH, so they are installed one at a time
+pub fn install_global_packages(global_packages: &JsonValue, should_update: bool, ctx: &Context, logger: Logger) -> JsonValue {
     let mut installed_packages = array![];
     if !global_packages.is_null() {
         for i in 0..global_packages.len() {
@@ -96,7 +154,7 @@ pub fn install_global_packages(global_packages: &JsonValue, should_update: bool,
                 Some(val_str) => val_str,
                 None => continue,
             };
-            match go::get(global_pkg, should_update) {
+            match go::get(global_pkg, should_update, ctx.offline) {
                 true => {
                     let _ = installed_packages.push(global_pkg);
                     logger.verbose("Global package", global_pkg)
@@ -108,77 +166,358 @@ pub fn install_global_packages(global_packages: &JsonValue, should_update: bool,
     installed_packages
 }
 
-pub fn install_git_packages(packages: &JsonValue, msg_title: &str, should_clean: bool, is_apply: bool, logger: Logger) -> JsonValue {
+pub fn install_git_packages(packages: &JsonValue, msg_title: &str, should_clean: bool, is_apply: bool, strip_vcs: bool, ctx: &Context, logger: Logger) -> JsonValue {
+    try_install_git_packages(packages, msg_title, should_clean, is_apply, strip_vcs, ctx, logger).0
+}
+
+// Returns the number of packages which failed along with the installed packages
+pub fn try_install_git_packages(packages: &JsonValue, msg_title: &str, should_clean: bool, is_apply: bool, strip_vcs: bool, ctx: &Context, logger: Logger) -> (JsonValue, usize) {
     if packages.is_null() {
-        return array![]
+        return (array![], 0)
     }
 
     let length = packages.len();
     if length == 0 {
-        return array![]
+        return (array![], 0)
     }
 
-    let pool = helpers::new_thread_pool();
+    let pool = match helpers::new_thread_pool(ctx) {
+        Ok(pool) => pool,
+        Err(e) => {
+            logger.fatal(e);
+            return (array![], length)
+        },
+    };
     let (tx, rx) = channel();
+    let batch = logger.batch(msg_title, length);
 
     for i in 0..length {
         let package = packages[i].clone();
         let c_tx = tx.clone();
+        let c_ctx = ctx.clone();
         pool.execute(move || {
-            update_package(package, should_clean, is_apply, c_tx, logger);
+            update_package(package, should_clean, is_apply, strip_vcs, c_tx, &c_ctx, logger);
         });
     }
 
     let mut git_packages = array![];
-    for pkg in rx.iter().take(length) {
-        logger.verbose(msg_title, match pkg[json_helper::IMPORT_KEY].as_str() {
-            Some(import_str) => import_str,
+    let mut failed = Vec::new();
+    for update in rx.iter().take(length) {
+        batch.done();
+        let pkg_import = match update.package[json_helper::IMPORT_KEY].as_str() {
+            Some(import_str) => import_str.to_owned(),
             None => continue,
-        });
+        };
+        match update.error {
+            Some(error) => failed.push(format!("{}: {}", pkg_import, error)),
+            None => logger.verbose(msg_title, pkg_import),
+        }
         if !is_apply {
-            let _ = git_packages.push(pkg);
+            let _ = git_packages.push(update.package);
+        }
+    }
+    if !failed.is_empty() {
+        failed.sort();
+        logger.error(format!("{} of {} package(s) failed, {} succeeded:\n\t{}", failed.len(), length, length - failed.len(), failed.join("\n\t")));
+    }
+    (git_packages, failed.len())
+}
+
+// The repositories are only read during the resolution, so the mirrors of the cache are used instead of clones in `vendor` directory
+pub struct VcsSource {
+    repositories: HashMap<String, PathBuf>,
+    clones: Vec<PathBuf>,
+    extracted: usize,
+    ctx: Context,
+    logger: Logger,
+}
+
+impl VcsSource {
+    pub fn new(ctx: &Context, logger: Logger) -> Self {
+        VcsSource {
+            repositories: HashMap::new(),
+            clones: Vec::new(),
+            extracted: 0,
+            ctx: ctx.clone(),
+            logger,
+        }
+    }
+
+    fn open(&mut self, pkg: &GitPackage) -> Result<Box<dyn Vcs>, String> {
+        let (repo_url, _, kind) = locate_package(pkg.import.as_str(), pkg.repo.as_deref(), pkg.vcs.as_deref(), &self.ctx)?;
+        if let Some(dir_path) = self.repositories.get(&pkg.import) {
+            return Ok(vcs::for_import(vcs::open(kind, dir_path.as_path(), &self.ctx, self.logger)?, pkg.import.as_str()))
+        }
+        let _task = self.logger.task(pkg.import.as_str());
+        let repo_url = config::clone_url(&self.ctx.config, repo_url.as_str());
+        let mut clone_ctx = self.ctx.clone();
+        if kind != vcs::Kind::Mod {
+            match vcs::update_mirror(kind, repo_url.as_str(), &self.ctx) {
+                Ok(Some(mirror_path)) => {
+                    let repository = vcs::open(kind, mirror_path.as_path(), &self.ctx, self.logger)?;
+                    self.logger.verbose("Fetch repository", &pkg.import);
+                    self.repositories.insert(pkg.import.clone(), mirror_path);
+                    return Ok(vcs::for_import(repository, pkg.import.as_str()))
+                },
+                Ok(None) => (),
+                Err(e) => {
+                    self.logger.error(format!("unable to update the mirror of `{}`, it is fetched directly: {}", repo_url, e));
+                    clone_ctx.cache_dir = None;
+                },
+            }
+        }
+
+        // Without the cache, the repository is cloned into a temporary directory which is removed after the resolution
+        let tmp_path_buf = env::temp_dir().join(format!("rubigo-{}-clone-{}", process::id(), self.clones.len()));
+        self.clones.push(tmp_path_buf.clone());
+        create_dir_all(tmp_path_buf.as_path()).map_err(|e| e.to_string())?;
+        let repository = vcs::clone(kind, repo_url.as_str(), tmp_path_buf.as_path(), &clone_ctx, self.logger)?;
+        self.logger.verbose("Clone repository", &pkg.import);
+        self.repositories.insert(pkg.import.clone(), tmp_path_buf);
+        Ok(vcs::for_import(repository, pkg.import.as_str()))
+    }
+}
+
+impl Drop for VcsSource {
+    fn drop(&mut self) {
+        for tmp_path_buf in &self.clones {
+            let _ = remove_dir_all(tmp_path_buf.as_path());
+        }
+    }
+}
+
+impl Source for VcsSource {
+    fn tags(&mut self, pkg: &GitPackage) -> Result<Vec<String>, String> {
+        self.open(pkg)?.tags()
+    }
+
+    fn dependencies(&mut self, pkg: &GitPackage, revision: &str) -> Result<Vec<GitPackage>, String> {
+        let repository = self.open(pkg)?;
+        let commit = match repository.resolve_revision(revision) {
+            Some(commit) => commit,
+            None => return Err(format!("unable to find the revision `{}`", revision)),
+        };
+
+        // The dependency files are read from the revision without checking it out
+        self.extracted += 1;
+        let tmp_path_buf = env::temp_dir().join(format!("rubigo-{}-{}", process::id(), self.extracted));
+        let mut result = Ok(());
+        for file_name in importer::DEPENDENCY_FILES {
+            let contents = match repository.read_file(commit.as_str(), file_name) {
+                Some(contents) => contents,
+                None => continue,
+            };
+            let file_path = tmp_path_buf.join(file_name);
+            result = file_path.parent().map_or(Ok(()), create_dir_all).and_then(|_| File::create(file_path.as_path())).and_then(|mut file| file.write_all(&contents));
+            if result.is_err() {
+                break
+            }
+        }
+        let dependencies = match result {
+            Ok(_) if tmp_path_buf.is_dir() => importer::dependencies(tmp_path_buf.as_path()),
+            Ok(_) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        };
+        let _ = remove_dir_all(tmp_path_buf.as_path());
+        dependencies.map_err(|e| e.to_string())
+    }
+}
+
+pub fn resolve_git_packages(packages: &JsonValue, locked_packages: &JsonValue, ctx: &Context, logger: Logger) -> Option<JsonValue> {
+    let roots: Vec<GitPackage> = packages.members().filter_map(GitPackage::from_json).collect();
+    let locked: Vec<Decision> = locked_packages.members().filter_map(|pkg| {
+        let locked_pkg = GitPackage::from_json(pkg)?;
+        Some(Decision {
+            version: resolver::tag_version(locked_pkg.version.as_str()),
+            revision: locked_pkg.version,
+            import: locked_pkg.import,
+            repo: locked_pkg.repo,
+            vcs: locked_pkg.vcs,
+            parent: pkg[json_helper::PARENT_KEY].as_str().map(|s| s.to_owned()),
+        })
+    }).collect();
+
+    let decisions = match resolver::resolve(&mut VcsSource::new(ctx, logger), &roots, &locked) {
+        Ok(decisions) => decisions,
+        Err(conflict) => {
+            logger.error(conflict);
+            return None
+        },
+    };
+
+    let mut resolved = array![];
+    for decision in decisions {
+        let mut pkg_json = match packages.members().find(|pkg| pkg[json_helper::IMPORT_KEY].as_str() == Some(decision.import.as_str())) {
+            Some(pkg) => pkg.clone(),
+            None => object!{
+                json_helper::IMPORT_KEY => decision.import.as_str()
+            },
+        };
+        if let Some(repo) = decision.repo {
+            pkg_json[json_helper::REPO_KEY] = repo.into();
+        }
+        if let Some(vcs) = decision.vcs {
+            pkg_json[json_helper::VCS_KEY] = vcs.into();
+        }
+        pkg_json[json_helper::VERSION_KEY] = match decision.version {
+            Some(version) => format!("={}", version).into(),
+            None => decision.revision.into(),
+        };
+        if let Some(parent) = decision.parent {
+            pkg_json[json_helper::PARENT_KEY] = parent.into();
+        }
+        let _ = resolved.push(pkg_json);
+    }
+    Some(resolved)
+}
+
+// A resolved tag is `=<version>` while the lock keeps the name of the tag, the other revisions are compared as they are
+pub fn is_locked_version(locked_version: &str, resolved_version: &str) -> bool {
+    match resolved_version.strip_prefix('=').and_then(|version| Version::parse(version).ok()) {
+        Some(version) => resolver::tag_version(locked_version) == Some(version),
+        None => locked_version == resolved_version,
+    }
+}
+
+pub fn unavailable_offline(git_packages: &JsonValue, global_packages: &JsonValue, should_clean: bool, ctx: &Context, logger: Logger) -> Vec<String> {
+    let mut unavailable = Vec::new();
+    for pkg in git_packages.members() {
+        let pkg_import = match pkg[json_helper::IMPORT_KEY].as_str() {
+            Some(import_str) => helpers::strip_url_scheme(import_str),
+            None => continue,
+        };
+        let (repo_url, pkg_path_buf, kind) = match locate_package(pkg_import.as_str(), pkg[json_helper::REPO_KEY].as_str(), pkg[json_helper::VCS_KEY].as_str(), ctx) {
+            Ok(location) => location,
+            Err(_) => {
+                unavailable.push(pkg_import);
+                continue
+            },
+        };
+        let is_vendored = !should_clean && vcs::open(kind, pkg_path_buf.as_path(), ctx, logger).is_ok();
+        let repo_url = config::clone_url(&ctx.config, repo_url.as_str());
+        let is_cached = cache::mirror_path(ctx.cache_dir.as_deref(), kind.name(), repo_url.as_str()).map_or(false, |mirror_path| mirror_path.is_dir());
+        if !is_vendored && !is_cached {
+            unavailable.push(pkg_import);
+        }
+    }
+    for pkg in global_packages.members().filter_map(|pkg| pkg.as_str()) {
+        if !go::is_installed(pkg) {
+            unavailable.push(pkg.to_owned());
+        }
+    }
+    unavailable
+}
+
+// Fails with the list of the packages which could not be satisfied offline
+pub fn check_offline(git_packages: &JsonValue, global_packages: &JsonValue, should_clean: bool, ctx: &Context, logger: Logger) -> Result<(), String> {
+    if !ctx.offline {
+        return Ok(())
+    }
+    let unavailable = unavailable_offline(git_packages, global_packages, should_clean, ctx, logger);
+    if unavailable.is_empty() {
+        return Ok(())
+    }
+    Err(format!("unable to satisfy {} package(s) offline, they are neither in `vendor` directory nor in the cache:\n\t{}", unavailable.len(), unavailable.join("\n\t")))
+}
+
+// Returns the clone url, the path in `vendor` directory and the version control system of a package
+// A package without `vcs` uses the `vcs` entry of the configuration, the `repo` of a module is the proxy which serves it
+pub fn locate_package(pkg_import: &str, repo: Option<&str>, vcs_name: Option<&str>, ctx: &Context) -> Result<(String, PathBuf, vcs::Kind), String> {
+    let config = &ctx.config;
+    let default_vcs = config[json_helper::VCS_KEY].as_str();
+    let meta_import = match (repo, vcs::Kind::from_option(vcs_name.or(default_vcs))) {
+        (Some(_), _) | (None, vcs::Kind::Mod) => None,
+        (None, _) => goget::resolve(pkg_import, ctx)?,
+    };
+    let (repo_url, repo_root, meta_vcs) = match meta_import {
+        Some(meta_import) => (Some(meta_import.repo), meta_import.prefix, Some(meta_import.vcs)),
+        None => (repo.map(|repo_str| repo_str.to_owned()), pkg_import.to_owned(), None),
+    };
+    let kind = match (vcs_name, meta_vcs) {
+        (Some(_), _) => vcs::Kind::from_option(vcs_name),
+        (None, None) => vcs::Kind::from_option(default_vcs),
+        (None, Some(meta_vcs)) => match vcs::Kind::from_name(meta_vcs.as_str()) {
+            Some(kind) => kind,
+            None => return Err(format!("`{}` is served by `{}` which is not supported", pkg_import, meta_vcs)),
+        },
+    };
+    let repo_url = match (kind, repo_url) {
+        (vcs::Kind::Mod, proxy) => modproxy::module_url(proxy.unwrap_or_else(|| modproxy::proxy_url(config)).as_str(), repo_root.as_str()),
+        (_, Some(url)) => url,
+        (_, None) => goget::default_repo(pkg_import, config),
+    };
+    Ok((repo_url, helpers::get_path_from_url(repo_root.as_str(), ctx.vendor_dir.as_path()), kind))
+}
+
+// The package is sent back even if it failed, so its previous lock entry is kept
+pub struct Update {
+    pub package: JsonValue,
+    pub error: Option<String>,
+}
+
+impl Update {
+    fn succeeded(package: JsonValue) -> Self {
+        Update {
+            package,
+            error: None,
         }
     }
-    git_packages
 }
 
-pub fn update_package(package: JsonValue, should_clean: bool, is_apply: bool, tx: Sender<JsonValue>, logger: Logger) {
+pub fn update_package(package: JsonValue, should_clean: bool, is_apply: bool, strip_vcs: bool, tx: Sender<Update>, ctx: &Context, logger: Logger) {
     let mut mut_pkg = package.clone();
     let pkg_import_raw = helpers::strip_url_scheme(match package[json_helper::IMPORT_KEY].as_str() {
         Some(import_str) => import_str,
-        None => {
-            logger.error("unable to get `import` value");
-            let _ = tx.send(mut_pkg);
-            return
-        },
+        None => return report_failure(&tx, mut_pkg, "unable to get `import` value", logger),
     });
-    let (http_import, modified_pkg_path) = helpers::modify_golang_org(pkg_import_raw.as_str());
     let pkg_import = pkg_import_raw.as_str();
-    let repo_url = match package[json_helper::REPO_KEY].as_str() {
-        Some(repo_str) => repo_str,
-        None => http_import.as_str(),
+    let _task = logger.task(pkg_import);
+    let (canonical_url, pkg_path_buf, kind) = match locate_package(pkg_import, package[json_helper::REPO_KEY].as_str(), package[json_helper::VCS_KEY].as_str(), ctx) {
+        Ok(location) => location,
+        Err(e) => return report_failure(&tx, mut_pkg, e, logger),
     };
-
-    let modified_pkg_import_path = if modified_pkg_path.is_some() {
-        modified_pkg_path.unwrap()
+    let repo_url = config::clone_url(&ctx.config, canonical_url.as_str());
+    if repo_url == canonical_url {
+        mut_pkg.remove(json_helper::URL_KEY);
     } else {
-        pkg_import_raw.clone()
-    };
-    let pkg_path_buf = helpers::get_path_from_url(modified_pkg_import_path.as_str());
+        mut_pkg[json_helper::URL_KEY] = repo_url.as_str().into();
+    }
     let pkg_path = pkg_path_buf.as_path();
+    let is_stripped = pkg_path.is_dir() && vcs::open(kind, pkg_path, ctx, logger).is_err();
+    let strip_vcs = strip_vcs || is_stripped;
+
+    // A stripped package has no repository, so its files are checked against the locked hash
+    if is_apply && is_stripped && !should_clean {
+        if let Some(hash) = package[json_helper::HASH_KEY].as_str() {
+            if integrity::hash_dir(pkg_path).ok().as_deref() == Some(hash) {
+                let _ = tx.send(Update::succeeded(mut_pkg));
+                return
+            }
+        }
+    }
+
     if should_clean && pkg_path.exists() {
         match remove_dir_all(pkg_path) {
             Ok(_) => logger.verbose("Clean package", pkg_path.to_str().unwrap_or("unknown")),
-            Err(e) => {
-                logger.error(format!("{} {}", pkg_import, e));
-                let _ = tx.send(mut_pkg);
-                return
-            }
+            Err(e) => return report_failure(&tx, mut_pkg, format!("{} {}", pkg_import, e), logger),
         }
     }
 
-    let repo = if should_clean || !pkg_path.is_dir() {
-        match create_dir_all(pkg_path) {
+    let work_path_buf = if is_stripped && !should_clean {
+        env::temp_dir().join(format!("rubigo-{}-{}", process::id(), pkg_import.replace(&['/', '\\', ':'][..], "_")))
+    } else {
+        pkg_path_buf.clone()
+    };
+    let work_path = work_path_buf.as_path();
+    let _work_dir = RemoveOnDrop(if work_path != pkg_path {
+        let _ = remove_dir_all(work_path);
+        Some(work_path_buf.clone())
+    } else {
+        None
+    });
+
+    let repo = if should_clean || !work_path.is_dir() {
+        match create_dir_all(work_path) {
             Ok(_) => logger.verbose("Create directory", &pkg_import),
             Err(e) => {
                 logger.fatal(e);
@@ -186,148 +525,172 @@ pub fn update_package(package: JsonValue, should_clean: bool, is_apply: bool, tx
             }
         }
 
-        match Repository::clone(repo_url, pkg_path) {
+        match vcs::clone(kind, repo_url.as_str(), work_path, ctx, logger) {
             Ok(repo) => {
                 logger.verbose("Clone repository", pkg_import);
-                repo
-            },
-            Err(e) => {
-                logger.error(format!("{} {}", pkg_import, e));
-                let _ = tx.send(mut_pkg);
-                return
+                vcs::for_import(repo, pkg_import)
             },
+            Err(e) => return report_failure(&tx, mut_pkg, format!("{} {}", pkg_import, e), logger),
         }
     } else {
-        match Repository::open(pkg_path) {
+        match vcs::open(kind, work_path, ctx, logger) {
             Ok(repo) => {
                 logger.verbose("Open repository", pkg_import);
+                if let Err(e) = repo.set_origin(repo_url.as_str()) {
+                    logger.error(format!("{} {}", pkg_import, e));
+                }
                 if !is_apply {
-                    match repo.remotes() {
-                        Ok(remotes) => match remotes.get(0) {
-                            Some(remote_name) => match repo.find_remote(remote_name) {
-                                Ok(mut remote) => match remote.fetch(&[], None, None) {
-                                    Ok(_) => {
-                                        logger.verbose("Fetch repository", pkg_import);
-                                        match repo.branches(Some(BranchType::Local)) {
-                                            Ok(branches) => for branch in branches {
-                                                match branch {
-                                                    Ok(br) => {
-                                                        let branch = br.0;
-                                                        let branch_ref_name = match branch.get().name() {
-                                                            Some(name) => name.to_owned(),
-                                                            None => continue,
-                                                        };
-                                                        let remote_name = match branch.upstream() {
-                                                            Ok(remote_branch) => match remote_branch.name() {
-                                                                Ok(remote_branch_name) => match remote_branch_name {
-                                                                    Some(name) => name.to_owned(),
-                                                                    None => continue,
-                                                                },
-                                                                _ => continue,
-                                                            },
-                                                            _ => continue,
-                                                        };
-                                                        let remote_object = match repo.revparse_single(remote_name.as_str()) {
-                                                            Ok(obj) => obj,
-                                                            _ => continue,
-                                                        };
-                                                        match repo.set_head(branch_ref_name.as_str()) {
-                                                            Ok(_) => (),
-                                                            _ => continue,
-                                                        }
-                                                        match repo.reset(&remote_object, ResetType::Hard, None) {
-                                                            Ok(_) => logger.verbose("Update branch", format!("{} {}", pkg_import, branch.name().unwrap_or(None).unwrap_or("unknown"))),
-                                                            _ => continue,
-                                                        }
-                                                    },
-                                                    _ => (),
-                                                }
-                                            },
-                                            _ => (),
-                                        }
-                                    },
-                                    Err(e) => {
-                                        logger.error(format!("{} {}", pkg_import, e));
-                                        let _ = tx.send(mut_pkg);
-                                        return
-                                    },
-                                },
-                                Err(e) => {
-                                    logger.error(format!("{} {}", pkg_import, e));
-                                    let _ = tx.send(mut_pkg);
-                                    return
-                                },
-                            },
-                            None => {
-                                logger.error(format!("{} unable to get remote name of", pkg_import));
-                                let _ = tx.send(mut_pkg);
-                                return
-                            },
-                        },
-                        Err(e) => {
-                            logger.error(format!("{} {}", pkg_import, e));
-                            let _ = tx.send(mut_pkg);
-                            return
-                        },
+                    match repo.fetch() {
+                        Ok(_) => logger.verbose("Fetch repository", pkg_import),
+                        Err(e) => return report_failure(&tx, mut_pkg, format!("{} {}", pkg_import, e), logger),
                     }
                 }
-                repo
+                vcs::for_import(repo, pkg_import)
             },
-            Err(e) => {
-                logger.error(format!("{} {}", pkg_import, e));
-                let _ = tx.send(mut_pkg);
-                return
-            }
+            Err(e) => return report_failure(&tx, mut_pkg, format!("{} {}", pkg_import, e), logger),
         }
     };
 
     let mut version = match package[json_helper::VERSION_KEY].as_str() {
         Some(version_str) => version_str.to_owned(),
-        None => {
-            logger.error(format!("{} unable to get `version` value", pkg_import));
-            let _ = tx.send(mut_pkg);
-            return
-        },
+        None => return report_failure(&tx, mut_pkg, format!("{} unable to get `version` value", pkg_import), logger),
     };
 
-    if !is_apply {
-        version = git_helper::get_latest_compat_version(&repo, version);
-    }
+    let locked_commit = if is_apply {
+        package[json_helper::COMMIT_KEY].as_str()
+    } else {
+        None
+    };
 
-    let version_object = match git_helper::get_revision_object(&repo, pkg_import.to_owned(), version, true, logger) {
-        Some(tup) => {
-            mut_pkg[json_helper::VERSION_KEY] = tup.1.clone().into();
-            tup.0
+    let commit = match locked_commit {
+        Some(commit) => {
+            if repo.resolve_revision(commit).is_none() {
+                match repo.fetch() {
+                    Ok(_) => logger.verbose("Fetch repository", pkg_import),
+                    Err(e) => logger.error(format!("{} {}", pkg_import, e)),
+                }
+            }
+            match repo.resolve_revision(commit) {
+                Some(_) => {
+                    if version != commit {
+                        match repo.resolve_revision(version.as_str()) {
+                            Some(ref id) if id == commit => (),
+                            Some(id) => logger.error(format!("the version `{}` of `{}` now points to `{}`, checking out the locked commit `{}`", version, pkg_import, id, commit)),
+                            None => logger.error(format!("the version `{}` of `{}` no longer exists, checking out the locked commit `{}`", version, pkg_import, commit)),
+                        }
+                    }
+                    commit.to_owned()
+                },
+                None => return report_failure(&tx, mut_pkg, format!("unable to find the locked commit `{}` of `{}`", commit, pkg_import), logger),
+            }
         },
         None => {
-            logger.error(format!("unable to parse the version of `{}`", pkg_import));
-            let _ = tx.send(mut_pkg);
-            return
-        }
+            if !is_apply {
+                let excluded: Vec<String> = package[json_helper::EXCLUDE_KEY].members().filter_map(|ex| ex.as_str().map(|s| s.to_owned())).collect();
+                version = vcs::latest_compat_version(repo.as_ref(), version, &excluded);
+            }
+
+            match vcs::resolve_or_latest(repo.as_ref(), pkg_import, version, logger) {
+                Some((commit, version)) => {
+                    mut_pkg[json_helper::VERSION_KEY] = version.into();
+                    commit
+                },
+                None => return report_failure(&tx, mut_pkg, format!("unable to parse the version of `{}`", pkg_import), logger),
+            }
+        },
     };
+    mut_pkg[json_helper::COMMIT_KEY] = commit.as_str().into();
 
-    match repo.set_head_detached(version_object.id()) {
+    match repo.checkout(commit.as_str()) {
         Ok(_) => (),
-        Err(e) => {
-            logger.error(format!("{} {}", pkg_import, e));
-            let _ = tx.send(mut_pkg);
-            return
-        },
+        Err(e) => return report_failure(&tx, mut_pkg, format!("{} {}", pkg_import, e), logger),
     }
 
-    match repo.reset(&version_object, ResetType::Hard, None){
-        Ok(_) => (),
-        Err(e) => {
-            logger.error(format!("{} {}", pkg_import, e));
-            let _ = tx.send(mut_pkg);
-            return
-        },
+    // The archive of a module is checked against the locked hash, as the proxy could serve other files for the same version
+    if let Some(sum) = repo.sum() {
+        match package[json_helper::SUM_KEY].as_str() {
+            Some(locked_sum) if is_apply && locked_sum != sum => return report_failure(&tx, mut_pkg, format!("the archive of `{}` does not match its locked sum `{}`, found `{}`", pkg_import, locked_sum, sum), logger),
+            _ => mut_pkg[json_helper::SUM_KEY] = sum.into(),
+        }
+    }
+
+    if strip_vcs {
+        drop(repo);
+        match strip_package(work_path, pkg_path) {
+            Ok(_) => logger.verbose("Strip package", pkg_import),
+            Err(e) => return report_failure(&tx, mut_pkg, format!("unable to strip the version control files of `{}`: {}", pkg_import, e), logger),
+        }
     }
 
-    let _ = tx.send(mut_pkg);
+    if !is_apply {
+        match integrity::hash_dir(pkg_path) {
+            Ok(hash) => mut_pkg[json_helper::HASH_KEY] = hash.into(),
+            Err(e) => return report_failure(&tx, mut_pkg, format!("unable to hash `{}`: {}", pkg_import, e), logger),
+        }
+    }
+
+    let _ = tx.send(Update::succeeded(mut_pkg));
 }
 
-fn parse_dir(dir_path: String, packages: Arc<Mutex<JsonValue>>, tx: Sender<Option<String>>, counter: Arc<Mutex<i32>>, logger: Logger) {
+fn report_failure<T: Display>(tx: &Sender<Update>, package: JsonValue, error: T, logger: Logger) {
+    let error = error.to_string();
+    logger.error(error.as_str());
+    let _ = tx.send(Update {
+        package,
+        error: Some(error),
+    });
+}
+
+struct RemoveOnDrop(Option<PathBuf>);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        if let Some(ref dir_path) = self.0 {
+            let _ = remove_dir_all(dir_path);
+        }
+    }
+}
+
+// The files are copied next to the package first, so it is replaced at once and never left half stripped
+fn strip_package(work_path: &Path, pkg_path: &Path) -> io::Result<()> {
+    let name = pkg_path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let stripped_path = pkg_path.with_file_name(format!(".{}.rubigo-strip-{}", name, process::id()));
+    let old_path = pkg_path.with_file_name(format!(".{}.rubigo-old-{}", name, process::id()));
+    let _stripped_dir = RemoveOnDrop(Some(stripped_path.clone()));
+    let _ = remove_dir_all(stripped_path.as_path());
+    copy_tree(work_path, stripped_path.as_path())?;
+    if pkg_path.exists() {
+        rename(pkg_path, old_path.as_path())?;
+    }
+    if let Err(e) = rename(stripped_path.as_path(), pkg_path) {
+        let _ = rename(old_path.as_path(), pkg_path);
+        return Err(e)
+    }
+    let _ = remove_dir_all(old_path.as_path());
+    Ok(())
+}
+
+fn copy_tree(src_path: &Path, dst_path: &Path) -> io::Result<()> {
+    create_dir_all(dst_path)?;
+    for entry in read_dir(src_path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if vcs::VCS_DIRS.iter().any(|dir| name == OsStr::new(dir)) {
+            continue
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(entry.path().as_path(), dst_path.join(&name).as_path())?;
+        } else if file_type.is_symlink() {
+            staging::copy_link(entry.path().as_path(), dst_path.join(&name).as_path())?;
+        } else {
+            copy(entry.path(), dst_path.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_dir(dir_path: String, packages: Arc<Mutex<JsonValue>>, tx: Sender<Option<String>>, counter: Arc<Mutex<i32>>, ctx: &Context, logger: Logger) {
     match read_dir(Path::new(dir_path.as_str())) {
         Ok(paths) => {
             for entry in paths {
@@ -356,6 +719,11 @@ fn parse_dir(dir_path: String, packages: Arc<Mutex<JsonValue>>, tx: Sender<Optio
                                     _ => (),
                                 }
                                 tx.send(None).unwrap();
+                            } else if path.join(".hg").as_path().is_dir() {
+                                if let (Some(pkg), Ok(mut ptr)) = (parse_hg_repository(path, ctx, logger), packages.lock()) {
+                                    let _ = ptr.push(pkg);
+                                }
+                                tx.send(None).unwrap();
                             } else {
                                 match path.to_str() {
                                     Some(path_str) => {
@@ -417,6 +785,9 @@ fn parse_repository(repo: Repository, logger: &Logger) -> Option<JsonValue> {
                     } else {
                         format!("{}", o_id).into()
                     };
+                    if let Ok(commit) = reference.peel(ObjectType::Commit) {
+                        pkg[json_helper::COMMIT_KEY] = format!("{}", commit.id()).into();
+                    }
                 },
                 None => return None,
             },
@@ -429,3 +800,17 @@ fn parse_repository(repo: Repository, logger: &Logger) -> Option<JsonValue> {
     pkg[json_helper::IMPORT_KEY] = pkg_import.into();
     Some(pkg)
 }
+
+// The version of a mercurial package is the revision of its working directory, which is the one checked out by Rubigo
+fn parse_hg_repository(path: &Path, ctx: &Context, logger: Logger) -> Option<JsonValue> {
+    let repo = vcs::open(vcs::Kind::Hg, path, ctx, logger).ok()?;
+    let commit = repo.resolve_revision(".")?;
+    let pkg_import = parse_import(path);
+    logger.verbose("Find package", &pkg_import);
+    Some(object!{
+        json_helper::IMPORT_KEY => pkg_import,
+        json_helper::VERSION_KEY => commit.as_str(),
+        json_helper::COMMIT_KEY => commit.as_str(),
+        json_helper::VCS_KEY => vcs::Kind::Hg.name()
+    })
+}
@@ -12,6 +12,20 @@ extern crate futures_cpupool;
 extern crate semver;
 extern crate regex;
 extern crate curl;
+extern crate sha2;
+extern crate toml;
+extern crate yaml_rust;
+extern crate base64;
+extern crate zip;
+#[macro_use]
+extern crate lazy_static;
+extern crate atty;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate winapi;
+#[cfg(windows)]
+extern crate kernel32;
 
 mod inner;
 mod controller;
@@ -22,6 +36,9 @@ use clap::{Arg, App, SubCommand, AppSettings};
 use std::process;
 use controller::*;
 use inner::logger::{Logger, Verbosity};
+use inner::{jobs, project_lock, staging};
+use inner::context::Context;
+use std::path::Path;
 
 const VERSION: &'static str = "1.0.4";
 
@@ -47,6 +64,20 @@ fn main() {
             .conflicts_with("verbose")
             .help("Print no output")
             .takes_value(false))
+        .arg(Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("N")
+            .help("The number of packages to install or update at once (Default: `jobs` of the configuration or the number of CPUs)")
+            .takes_value(true))
+        .arg(Arg::with_name("no-wait")
+            .long("no-wait")
+            .help("Fail instead of waiting if another Rubigo process is changing the project")
+            .takes_value(false))
+        .arg(Arg::with_name("offline")
+            .long("offline")
+            .help("Use only the cached repositories and `vendor` directory without accessing the network (Default: `RUBIGO_OFFLINE`)")
+            .takes_value(false))
         .subcommand(SubCommand::with_name("new")
             .visible_alias("create")
             .arg(Arg::with_name("name")
@@ -67,6 +98,22 @@ fn main() {
         .subcommand(SubCommand::with_name("init")
             .visible_alias("start")
             .about("Initialize Rubigo project in an existing directory"))
+        .subcommand(SubCommand::with_name("import")
+            .visible_alias("migrate")
+            .arg(Arg::with_name("tool")
+                .help("The dependency tool to import from (Default: detected from the files in this directory)")
+                .possible_values(&["dep", "glide", "govendor", "godep", "gomod"]))
+            .about("Create `rubigo.json` and `rubigo.lock` from the manifest of another dependency tool"))
+        .subcommand(SubCommand::with_name("export")
+            .arg(Arg::with_name("format")
+                .help("The format to export the dependencies to")
+                .possible_values(&["gomod"])
+                .required(true))
+            .arg(Arg::with_name("vendor")
+                .long("vendor")
+                .help("Write `vendor/modules.txt` to build using `go build -mod=vendor`")
+                .takes_value(false))
+            .about("Generate `go.mod` and `go.sum` from `rubigo.json` and `rubigo.lock`"))
         .subcommand(SubCommand::with_name("reset")
             .visible_alias("sync")
             .about("Update `rubigo.json` and `rubigo.lock` to the list of packages in `vendor` directory"))
@@ -84,6 +131,15 @@ fn main() {
                 .required(false)
                 .conflicts_with_all(&["local", "global"])
                 .takes_value(true))
+            .arg(Arg::with_name("vcs")
+                .long("vcs")
+                .value_name("vcs")
+                .help("The version control system of the package, or `mod` to download it from a Go module proxy (Default: git)")
+                .possible_values(&["git", "hg", "mod"])
+                .require_equals(true)
+                .required(false)
+                .conflicts_with_all(&["local", "global"])
+                .takes_value(true))
             .arg(Arg::with_name("global")
                 .short("g")
                 .long("global")
@@ -118,6 +174,18 @@ fn main() {
                 .conflicts_with("package")
                 .takes_value(false))
             .about("Update one or all packages and apply the changes of `rubigo.json` to `rubigo.lock` and packages in `vendor` directory"))
+        .subcommand(SubCommand::with_name("tidy")
+            .arg(Arg::with_name("add")
+                .short("a")
+                .long("add")
+                .help("Add the missing packages using `get` sub command")
+                .takes_value(false))
+            .arg(Arg::with_name("remove")
+                .short("r")
+                .long("remove")
+                .help("Remove the unused packages using `remove` sub command")
+                .takes_value(false))
+            .about("Report the packages of `rubigo.json` which are not imported and the imports which are missing from `rubigo.json`"))
         .subcommand(SubCommand::with_name("list")
             .visible_alias("ls")
             .arg(Arg::with_name("all")
@@ -142,6 +210,42 @@ fn main() {
                 .help("List remote (git) packages from `rubigo.lock` file")
                 .takes_value(false))
             .about("Display a list of dependencies from `rubigo.lock` file"))
+        .subcommand(SubCommand::with_name("cache")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .visible_alias("ls")
+                .about("Display the cached repositories and their sizes"))
+            .subcommand(SubCommand::with_name("clean")
+                .about("Delete all cached repositories"))
+            .subcommand(SubCommand::with_name("gc")
+                .arg(Arg::with_name("max-size")
+                    .long("max-size")
+                    .value_name("size")
+                    .help("The maximum size of cache, e.g. `500M` or `2G` (Default: `RUBIGO_CACHE_MAX_SIZE` or 2G)")
+                    .require_equals(true)
+                    .takes_value(true))
+                .about("Delete the least recently used repositories until the cache fits in its maximum size"))
+            .about("Manage the user-level cache of repositories which is shared between projects"))
+        .subcommand(SubCommand::with_name("outdated")
+            .arg(Arg::with_name("json")
+                .long("json")
+                .help("Print the report in JSON format")
+                .takes_value(false))
+            .about("Display the locked, wanted and latest versions of git packages"))
+        .subcommand(SubCommand::with_name("why")
+            .arg(Arg::with_name("import")
+                .help("The import path of package")
+                .required(true))
+            .about("Display every chain of packages from `rubigo.json` which requires a package"))
+        .subcommand(SubCommand::with_name("graph")
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .help("The format of graph")
+                .possible_values(&["dot", "json"])
+                .default_value("dot")
+                .takes_value(true))
+            .about("Display the dependency graph of project"))
         .subcommand(SubCommand::with_name("apply")
             .visible_alias("install")
             .arg(Arg::with_name("clean")
@@ -149,7 +253,16 @@ fn main() {
                 .long("clean")
                 .help("Remove the package directory and clone from the repository")
                 .takes_value(false))
+            .arg(Arg::with_name("strip-vcs")
+                .long("strip-vcs")
+                .help("Remove the `.git` and `.hg` directories of packages, which are then tracked only by `rubigo.lock`")
+                .takes_value(false))
             .about("Apply the changes of `rubigo.lock` to packages in `vendor` directory"))
+        .subcommand(SubCommand::with_name("verify")
+            .about("Check that the packages in `vendor` directory match the hashes in `rubigo.lock`"))
+        .subcommand(SubCommand::with_name("validate")
+            .visible_alias("check")
+            .about("Check `rubigo.json` and `rubigo.lock` files against the manifest format"))
         .subcommand(SubCommand::with_name("info")
             .visible_alias("about")
             .about("Display the information about this Rubigo project"))
@@ -163,6 +276,41 @@ fn main() {
         Verbosity::Low
     });
 
+    // The configuration is read once from the project in the current directory
+    let mut ctx = match Context::load(Path::new(".")) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            logger.fatal(e);
+            return
+        },
+    };
+
+    if matches.is_present("offline") {
+        ctx.offline = true;
+    }
+
+    if let Some(jobs_arg) = matches.value_of("jobs") {
+        match jobs::parse_count(jobs_arg) {
+            Some(jobs) => ctx.jobs = Some(jobs),
+            None => logger.fatal(format!("`--jobs` should be a positive integer, found `{}`", jobs_arg)),
+        }
+    }
+
+    // `init` and `import` create a project, the other commands fail without one
+    let _project_lock = match matches.subcommand_name() {
+        Some(name) if project_lock::LOCKED_COMMANDS.contains(&name) && (name == "init" || name == "import" || Path::new("rubigo.json").is_file()) => {
+            // An update which was interrupted is finished or discarded before the project is changed again
+            match project_lock::lock(project_lock::lock_path().as_path(), !matches.is_present("no-wait"), logger).and_then(|lock| staging::recover(ctx.vendor_dir.as_path(), logger).map(|_| lock)) {
+                Ok(lock) => Some(lock),
+                Err(e) => {
+                    logger.fatal(e);
+                    return
+                },
+            }
+        },
+        _ => None,
+    };
+
     match matches.subcommand_name() {
         Some("apply") => {
             let apply_matches = match matches.subcommand_matches("apply") {
@@ -172,7 +320,7 @@ fn main() {
                     return
                 },
             };
-            project::apply(apply_matches.is_present("clean"), logger)
+            project::apply(apply_matches.is_present("clean"), apply_matches.is_present("strip-vcs"), &ctx, logger)
         },
         Some("get") => {
             let get_matches = match matches.subcommand_matches("get") {
@@ -188,11 +336,82 @@ fn main() {
                     logger.fatal("unable to get `package` argument of `get` sub command");
                     return
                 }
-            }, get_matches.value_of("repository"), matches.is_present("no-prompt"), get_matches.is_present("global"), get_matches.is_present("local"), logger);
+            }, get_matches.value_of("repository"), get_matches.value_of("vcs"), matches.is_present("no-prompt"), get_matches.is_present("global"), get_matches.is_present("local"), &ctx, logger);
         },
         Some("info") => info::display(&logger),
-        Some("init") => project::init(logger),
-        Some("reset") => project::reset(matches.is_present("no-prompt"), logger),
+        Some("init") => project::init(&ctx, logger),
+        Some("import") => {
+            let import_matches = match matches.subcommand_matches("import") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `import` sub command");
+                    return
+                },
+            };
+            import::import(import_matches.value_of("tool"), &ctx, logger)
+        },
+        Some("export") => {
+            let export_matches = match matches.subcommand_matches("export") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `export` sub command");
+                    return
+                },
+            };
+            match export_matches.value_of("format") {
+                Some("gomod") => export::export_gomod(export_matches.is_present("vendor"), matches.is_present("no-prompt"), logger),
+                _ => logger.fatal("unable to get `format` argument of `export` sub command"),
+            }
+        },
+        Some("outdated") => {
+            let outdated_matches = match matches.subcommand_matches("outdated") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `outdated` sub command");
+                    return
+                },
+            };
+            outdated::outdated(outdated_matches.is_present("json"), &ctx, logger)
+        },
+        Some("why") => {
+            let why_matches = match matches.subcommand_matches("why") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `why` sub command");
+                    return
+                },
+            };
+            match why_matches.value_of("import") {
+                Some(import) => graph::why(import, logger),
+                None => logger.fatal("unable to get `import` argument of `why` sub command"),
+            }
+        },
+        Some("graph") => {
+            let graph_matches = match matches.subcommand_matches("graph") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `graph` sub command");
+                    return
+                },
+            };
+            graph::graph(graph_matches.value_of("format").unwrap_or("dot"), logger)
+        },
+        Some("cache") => {
+            let cache_matches = match matches.subcommand_matches("cache") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `cache` sub command");
+                    return
+                },
+            };
+            match cache_matches.subcommand() {
+                ("list", _) => cache::list(logger),
+                ("clean", _) => cache::clean(matches.is_present("no-prompt"), logger),
+                ("gc", Some(gc_matches)) => cache::gc(gc_matches.value_of("max-size"), logger),
+                _ => logger.fatal("unable to get the sub command of `cache` sub command"),
+            }
+        },
+        Some("reset") => project::reset(matches.is_present("no-prompt"), &ctx, logger),
         Some("list") => {
             let list_matches = match matches.subcommand_matches("list") {
                 Some(args) => args,
@@ -247,11 +466,23 @@ fn main() {
                         logger.fatal("unable to get `package` argument of `update` sub command");
                         return
                     },
-                }), update_matches.is_present("clean"), logger)
+                }), update_matches.is_present("clean"), &ctx, logger)
             } else {
-                package::update(None, update_matches.is_present("clean"), logger)
+                package::update(None, update_matches.is_present("clean"), &ctx, logger)
             }
         },
+        Some("tidy") => {
+            let tidy_matches = match matches.subcommand_matches("tidy") {
+                Some(args) => args,
+                None => {
+                    logger.fatal("unable to get argument of `tidy` sub command");
+                    return
+                },
+            };
+            tidy::tidy(tidy_matches.is_present("add"), tidy_matches.is_present("remove"), matches.is_present("no-prompt"), &ctx, logger)
+        },
+        Some("validate") => validate::validate(&logger),
+        Some("verify") => verify::verify(&logger),
         _ => {
             logger.error("No sub command has been provided. Please run `rubigo --help` for more information");
             process::exit(1)